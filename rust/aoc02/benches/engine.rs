@@ -0,0 +1,114 @@
+// Compare the plain interpreter with the pre-decoded engine.
+// Run with `cargo bench`.
+
+use std::time::{Duration, Instant};
+
+use aoc02::asm::assemble;
+use aoc02::cached::CachedComputer;
+use aoc02::IntComputer;
+
+const DAY2: &str = include_str!("../input/input.txt");
+
+// Nested countdown loops, a few million instructions in total
+const LOOPS: &str = "
+        outer:  add   [inner_init], 0, [inner]
+        inner_loop:
+                mul   [inner], 3, [tmp]
+                lt    [tmp], 100, [flag]
+                add   [inner], -1, [inner]
+                jnz   [inner], inner_loop
+                add   [outer_count], -1, [outer_count]
+                jnz   [outer_count], outer
+                out   [tmp]
+                hlt
+        inner:       .data 0
+        inner_init:  .data 1000
+        outer_count: .data 1000
+        tmp:         .data 0
+        flag:        .data 0
+";
+
+fn time<F: FnMut() -> i64>(mut f: F) -> (Duration, i64) {
+    // best of a few runs to smooth out noise
+    let mut best = None;
+    let mut result = 0;
+    for _ in 0..5 {
+        let start = Instant::now();
+        result = f();
+        let elapsed = start.elapsed();
+        if best.is_none_or(|b| elapsed < b) {
+            best = Some(elapsed);
+        }
+    }
+    (best.unwrap(), result)
+}
+
+fn day2_plain(program: &IntComputer) -> i64 {
+    let mut intcomp = program.clone();
+    let initial = intcomp.snapshot();
+    for noun in 0..100 {
+        for verb in 0..100 {
+            intcomp.restore(&initial);
+            intcomp.memory.set(1, noun).unwrap();
+            intcomp.memory.set(2, verb).unwrap();
+            intcomp.run().unwrap();
+            if intcomp.memory[0] == 19690720 {
+                return 100 * noun + verb;
+            }
+        }
+    }
+    -1
+}
+
+// Straight-line code that runs once per reset, so there is nothing for the
+// cache to reuse. Kept to show the decoding overhead.
+fn day2_cached(program: &IntComputer) -> i64 {
+    let initial = program.snapshot();
+    let mut engine = CachedComputer::new(program.clone());
+    for noun in 0..100 {
+        for verb in 0..100 {
+            let intcomp = engine.machine_mut();
+            intcomp.restore(&initial);
+            intcomp.memory.set(1, noun).unwrap();
+            intcomp.memory.set(2, verb).unwrap();
+            engine.run().unwrap();
+            if engine.machine().memory[0] == 19690720 {
+                return 100 * noun + verb;
+            }
+        }
+    }
+    -1
+}
+
+fn report(name: &str, plain: (Duration, i64), cached: (Duration, i64)) {
+    assert_eq!(plain.1, cached.1, "{}: engines disagree", name);
+    println!(
+        "{:<8} plain {:>10.3?}  cached {:>10.3?}  speedup {:.2}x",
+        name,
+        plain.0,
+        cached.0,
+        plain.0.as_secs_f64() / cached.0.as_secs_f64()
+    );
+}
+
+fn main() {
+    let program: IntComputer = DAY2.parse().unwrap();
+    report(
+        "day2",
+        time(|| day2_plain(&program)),
+        time(|| day2_cached(&program)),
+    );
+
+    let program = IntComputer::new(assemble(LOOPS).unwrap());
+    let plain = time(|| {
+        let mut intcomp = program.clone();
+        intcomp.run().unwrap();
+        intcomp.pop_output().unwrap()
+    });
+    let cached = time(|| {
+        let mut engine = CachedComputer::new(program.clone());
+        engine.run().unwrap();
+        engine.pop_output().unwrap()
+    });
+    report("loops", plain, cached);
+}
@@ -0,0 +1,166 @@
+use crate::{IntComputer, IntcodeError, Result, Status};
+
+// Machines wired in series: every amplifier reads its phase setting first,
+// then a signal, and its output becomes the next amplifier's signal. In
+// feedback mode the last amplifier feeds the first one until it halts.
+pub struct AmplifierChain {
+    pub amps: Vec<IntComputer>,
+}
+
+impl AmplifierChain {
+    pub fn new(program: &IntComputer, phases: &[i64]) -> AmplifierChain {
+        let amps = phases
+            .iter()
+            .map(|&phase| {
+                let mut amp = program.clone();
+                amp.push_input(phase);
+                amp
+            })
+            .collect();
+        AmplifierChain { amps }
+    }
+
+    // Every amplifier runs until it blocks and passes on everything it
+    // produced. The final signal is the last value out of the last amplifier.
+    pub fn run(&mut self, input: i64, feedback: bool) -> Result<i64> {
+        let mut signals = vec![input];
+        let mut signal = input;
+        loop {
+            let mut last_halted = true;
+            for amp in self.amps.iter_mut() {
+                amp.input.extend(signals.drain(..));
+                let status = amp.run_until_blocked()?;
+                signals.extend(amp.output.drain(..));
+                if status == Status::NeedsInput && signals.is_empty() {
+                    return Err(IntcodeError::InputExhausted {
+                        addr: amp.cursor,
+                        op: amp.get_op(),
+                    });
+                }
+                last_halted = status == Status::Halted;
+            }
+
+            if let Some(&last) = signals.last() {
+                signal = last;
+            }
+            if !feedback || last_halted {
+                return Ok(signal);
+            }
+        }
+    }
+}
+
+// All orderings of `items` (Heap's algorithm)
+pub fn permutations(items: &[i64]) -> Vec<Vec<i64>> {
+    fn heap(k: usize, items: &mut Vec<i64>, out: &mut Vec<Vec<i64>>) {
+        if k <= 1 {
+            out.push(items.clone());
+            return;
+        }
+        for i in 0..k - 1 {
+            heap(k - 1, items, out);
+            let j = if k.is_multiple_of(2) { i } else { 0 };
+            items.swap(j, k - 1);
+        }
+        heap(k - 1, items, out);
+    }
+
+    let mut out = vec![];
+    heap(items.len(), &mut items.to_vec(), &mut out);
+    out
+}
+
+// Try every ordering of `phases` and return the largest final signal along
+// with the phase sequence that produced it.
+pub fn max_signal(
+    program: &IntComputer,
+    phases: &[i64],
+    feedback: bool,
+) -> Result<(i64, Vec<i64>)> {
+    let mut best: Option<(i64, Vec<i64>)> = None;
+    for perm in permutations(phases) {
+        let signal = AmplifierChain::new(program, &perm).run(0, feedback)?;
+        if best.as_ref().is_none_or(|b| signal > b.0) {
+            best = Some((signal, perm));
+        }
+    }
+    Ok(best.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    #[test]
+    fn permutations_test() {
+        let mut perms = permutations(&[1, 2, 3]);
+        perms.sort();
+        assert_eq!(
+            perms,
+            [
+                [1, 2, 3],
+                [1, 3, 2],
+                [2, 1, 3],
+                [2, 3, 1],
+                [3, 1, 2],
+                [3, 2, 1]
+            ]
+        );
+        assert_eq!(permutations(&[0, 1, 2, 3, 4]).len(), 120);
+    }
+
+    #[test]
+    fn series_test() {
+        let program: IntComputer = "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0"
+            .parse()
+            .unwrap();
+        let signal = AmplifierChain::new(&program, &[4, 3, 2, 1, 0])
+            .run(0, false)
+            .unwrap();
+        assert_eq!(signal, 43210);
+
+        let (signal, phases) = max_signal(&program, &[0, 1, 2, 3, 4], false).unwrap();
+        assert_eq!((signal, phases), (43210, vec![4, 3, 2, 1, 0]));
+    }
+
+    #[test]
+    fn feedback_test() {
+        let program: IntComputer = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,\
+                                    27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5"
+            .parse()
+            .unwrap();
+        let (signal, phases) = max_signal(&program, &[5, 6, 7, 8, 9], true).unwrap();
+        assert_eq!((signal, phases), (139629729, vec![9, 8, 7, 6, 5]));
+    }
+
+    #[test]
+    fn feedback_all_outputs_test() {
+        // sends two values per round, so both have to make it back
+        let source = "
+                    in    [phase]
+                    in    [a]
+                    add   [a], 1, [a]
+                    out   [a]
+                    add   [a], 1, [a]
+                    out   [a]
+            loop:   in    [a]
+                    in    [b]
+                    add   [a], [b], [a]
+                    out   [a]
+                    add   [a], 1, [a]
+                    out   [a]
+                    lt    [a], 20, [t]
+                    jnz   [t], loop
+                    hlt
+            phase:  .data 0
+            a:      .data 0
+            b:      .data 0
+            t:      .data 0
+        ";
+        let program = IntComputer::new(assemble(source).unwrap());
+        let mut chain = AmplifierChain::new(&program, &[0]);
+        // 0 -> (1, 2) -> (3, 4) -> (7, 8) -> (15, 16) -> (31, 32)
+        assert_eq!(chain.run(0, true).unwrap(), 32);
+    }
+}
@@ -0,0 +1,156 @@
+use std::io::{self, BufRead, Write};
+use std::mem;
+
+use crate::{IntComputer, Result, Status};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    Line(String),
+    Raw(i64),
+}
+
+impl IntComputer {
+    pub fn push_str(&mut self, text: &str) {
+        self.input.extend(text.chars().map(|c| c as i64));
+    }
+}
+
+// Text front end for programs that talk in ASCII. Output is split into
+// lines; values outside the ASCII range (usually a final answer) come
+// through as raw numbers.
+pub struct AsciiComputer {
+    pub intcomp: IntComputer,
+    partial: String,
+}
+
+impl AsciiComputer {
+    pub fn new(intcomp: IntComputer) -> AsciiComputer {
+        AsciiComputer {
+            intcomp,
+            partial: String::new(),
+        }
+    }
+
+    pub fn send_line(&mut self, line: &str) {
+        self.intcomp.push_str(line);
+        self.intcomp.push_input('\n' as i64);
+    }
+
+    fn flush(&mut self, out: &mut Vec<Output>) {
+        if !self.partial.is_empty() {
+            out.push(Output::Line(mem::take(&mut self.partial)));
+        }
+    }
+
+    // Run until the program needs input or halts, appending what it printed
+    // to `out`. Text left without a newline when it stops, such as a prompt,
+    // is returned as a line of its own.
+    pub fn run(&mut self, out: &mut Vec<Output>) -> Result<Status> {
+        let result = loop {
+            let result = self.intcomp.run();
+            while let Some(v) = self.intcomp.pop_output() {
+                match v {
+                    10 => out.push(Output::Line(mem::take(&mut self.partial))),
+                    0..=127 => self.partial.push(v as u8 as char),
+                    _ => {
+                        self.flush(out);
+                        out.push(Output::Raw(v));
+                    }
+                }
+            }
+            if result != Ok(Status::ProducedOutput) {
+                break result;
+            }
+        };
+        self.flush(out);
+        result
+    }
+
+    // Interactive session: print the program's output, and feed it one line
+    // from `input` whenever it asks for more. Ends when the program halts or
+    // faults, or when `input` runs out.
+    pub fn session<R: BufRead, W: Write>(&mut self, input: R, mut out: W) -> io::Result<()> {
+        let mut lines = input.lines();
+        loop {
+            let mut output = vec![];
+            let result = self.run(&mut output);
+            for item in output {
+                match item {
+                    Output::Line(line) => writeln!(out, "{}", line)?,
+                    Output::Raw(v) => writeln!(out, "{}", v)?,
+                }
+            }
+            match result {
+                Ok(Status::NeedsInput) => (),
+                Ok(_) => break,
+                Err(e) => {
+                    writeln!(out, "fault: {}", e)?;
+                    break;
+                }
+            }
+            out.flush()?;
+            match lines.next() {
+                Some(line) => self.send_line(&line?),
+                None => break,
+            }
+        }
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    // prompts with "?", echoes one line, then reports its length raw
+    const ECHO: &str = "
+                out   63
+        loop:   in    [c]
+                eq    [c], 10, [t]
+                jnz   [t], done
+                out   [c]
+                add   [n], 1000, [n]
+                jnz   1, loop
+        done:   out   10
+                out   [n]
+                hlt
+        c:      .data 0
+        t:      .data 0
+        n:      .data 0
+    ";
+
+    fn echo() -> AsciiComputer {
+        AsciiComputer::new(IntComputer::new(assemble(ECHO).unwrap()))
+    }
+
+    #[test]
+    fn run_test() {
+        let mut ascii = echo();
+        let mut out = vec![];
+        assert_eq!(ascii.run(&mut out), Ok(Status::NeedsInput));
+        assert_eq!(out, [Output::Line("?".to_string())]);
+
+        ascii.send_line("abc");
+        out.clear();
+        assert_eq!(ascii.run(&mut out), Ok(Status::Halted));
+        assert_eq!(out, [Output::Line("abc".to_string()), Output::Raw(3000)]);
+    }
+
+    #[test]
+    fn session_test() {
+        let mut out = vec![];
+        echo()
+            .session("hello\nignored\n".as_bytes(), &mut out)
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "?\nhello\n5000\n");
+
+        let mut out = vec![];
+        let mut ascii = AsciiComputer::new("104,72,104,10,77".parse().unwrap());
+        ascii.session("".as_bytes(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "H\nfault: unknown op code 77 at address 4\n"
+        );
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::{Instruction, Mode, Opcode};
+
+// Largest region a single `.zero` may reserve
+const MAX_ZERO: usize = 1 << 20;
+
+// Assembly syntax, one statement per line:
+//
+//     loop:   add   [rb+1], 5, [total]   ; comment
+//             jnz   [flag], loop
+//             hlt
+//     total:  .data 0
+//             .zero 4
+//
+// Operands use the same syntax as the disassembler (`5` immediate, `[5]`
+// position, `[rb+5]` relative). A label can stand in for any number.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    UnknownMnemonic(String),
+    WrongOperandCount { expected: usize, found: usize },
+    InvalidOperand(String),
+    ImmediateDestination,
+    DuplicateLabel(String),
+    UndefinedLabel(String),
+    ZeroTooLarge(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub kind: AsmErrorKind,
+    pub line: usize,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            AsmErrorKind::UnknownMnemonic(ref name) => write!(f, "unknown mnemonic {:?}", name)?,
+            AsmErrorKind::WrongOperandCount { expected, found } => {
+                write!(f, "expected {} operands, found {}", expected, found)?
+            }
+            AsmErrorKind::InvalidOperand(ref text) => write!(f, "invalid operand {:?}", text)?,
+            AsmErrorKind::ImmediateDestination => {
+                write!(f, "destination operand cannot be immediate")?
+            }
+            AsmErrorKind::DuplicateLabel(ref name) => write!(f, "duplicate label {:?}", name)?,
+            AsmErrorKind::UndefinedLabel(ref name) => write!(f, "undefined label {:?}", name)?,
+            AsmErrorKind::ZeroTooLarge(count) => {
+                write!(f, ".zero {} is larger than {} cells", count, MAX_ZERO)?
+            }
+        }
+        write!(f, " on line {}", self.line)
+    }
+}
+
+impl Error for AsmError {}
+
+#[derive(Debug)]
+enum Value {
+    Number(i64),
+    Label(String),
+}
+
+#[derive(Debug)]
+enum Statement {
+    Instr(Instruction, Vec<Value>),
+    Data(Vec<Value>),
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        }
+        _ => false,
+    }
+}
+
+fn parse_value(text: &str) -> Option<Value> {
+    let text = text.trim();
+    if let Ok(n) = text.parse() {
+        Some(Value::Number(n))
+    } else if is_label(text) && text != "rb" {
+        Some(Value::Label(text.to_string()))
+    } else {
+        None
+    }
+}
+
+fn parse_operand(text: &str) -> Option<(Mode, Value)> {
+    let text = text.trim();
+    if !text.starts_with('[') {
+        return parse_value(text).map(|v| (Mode::Immediate, v));
+    }
+    if !text.ends_with(']') {
+        return None;
+    }
+
+    let inner = text[1..text.len() - 1].trim();
+    if !inner.starts_with("rb") || (is_label(inner) && inner != "rb") {
+        return parse_value(inner).map(|v| (Mode::Position, v));
+    }
+
+    let offset = inner[2..].trim();
+    if offset.is_empty() {
+        return Some((Mode::Relative, Value::Number(0)));
+    }
+    // the sign belongs to `rb+`/`rb-`, so the rest is digits or a label
+    let (sign, rest) = offset.split_at(1);
+    let rest = rest.trim();
+    if rest.starts_with('+') || rest.starts_with('-') {
+        return None;
+    }
+    let value = match (sign, rest.parse::<u64>()) {
+        ("+", Ok(n)) => Value::Number(0i64.checked_add_unsigned(n)?),
+        ("-", Ok(n)) => Value::Number(0i64.checked_sub_unsigned(n)?),
+        ("+", Err(_)) => parse_value(rest)?,
+        _ => return None,
+    };
+    Some((Mode::Relative, value))
+}
+
+fn split_operands(text: &str) -> Vec<&str> {
+    if text.trim().is_empty() {
+        vec![]
+    } else {
+        text.split(',').map(|s| s.trim()).collect()
+    }
+}
+
+fn parse_statement(text: &str, line: usize) -> Result<Statement, AsmError> {
+    let err = |kind| AsmError { kind, line };
+    let (name, rest) = match text.find(char::is_whitespace) {
+        Some(pos) => (&text[..pos], &text[pos..]),
+        None => (text, ""),
+    };
+    let operands = split_operands(rest);
+
+    match name {
+        ".data" => {
+            let values = operands
+                .iter()
+                .map(|&op| {
+                    parse_value(op).ok_or_else(|| err(AsmErrorKind::InvalidOperand(op.to_string())))
+                })
+                .collect::<Result<Vec<Value>, AsmError>>()?;
+            Ok(Statement::Data(values))
+        }
+        ".zero" => {
+            let count: usize = match operands[..] {
+                [op] => op
+                    .parse()
+                    .map_err(|_| err(AsmErrorKind::InvalidOperand(op.to_string())))?,
+                _ => {
+                    return Err(err(AsmErrorKind::WrongOperandCount {
+                        expected: 1,
+                        found: operands.len(),
+                    }))
+                }
+            };
+            if count > MAX_ZERO {
+                return Err(err(AsmErrorKind::ZeroTooLarge(count)));
+            }
+            Ok(Statement::Data(
+                (0..count).map(|_| Value::Number(0)).collect(),
+            ))
+        }
+        _ => {
+            let opcode = Opcode::from_mnemonic(name)
+                .ok_or_else(|| err(AsmErrorKind::UnknownMnemonic(name.to_string())))?;
+            if operands.len() != opcode.num_params() {
+                return Err(err(AsmErrorKind::WrongOperandCount {
+                    expected: opcode.num_params(),
+                    found: operands.len(),
+                }));
+            }
+
+            let mut modes = [Mode::Position; 3];
+            let mut values = vec![];
+            for (n, &op) in operands.iter().enumerate() {
+                let (mode, value) = parse_operand(op)
+                    .ok_or_else(|| err(AsmErrorKind::InvalidOperand(op.to_string())))?;
+                if mode == Mode::Immediate && opcode.write_param() == Some(n) {
+                    return Err(err(AsmErrorKind::ImmediateDestination));
+                }
+                modes[n] = mode;
+                values.push(value);
+            }
+            Ok(Statement::Instr(Instruction { opcode, modes }, values))
+        }
+    }
+}
+
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut statements = vec![];
+    let mut addr = 0;
+
+    for (lineno, line) in source.lines().enumerate() {
+        let lineno = lineno + 1;
+        let mut text = match line.find(';') {
+            Some(pos) => &line[..pos],
+            None => line,
+        }
+        .trim();
+
+        while let Some(pos) = text.find(':') {
+            let name = text[..pos].trim();
+            if !is_label(name) {
+                break;
+            }
+            if labels.insert(name.to_string(), addr).is_some() {
+                return Err(AsmError {
+                    kind: AsmErrorKind::DuplicateLabel(name.to_string()),
+                    line: lineno,
+                });
+            }
+            text = text[pos + 1..].trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let stmt = parse_statement(text, lineno)?;
+        addr += match stmt {
+            Statement::Instr(instr, _) => instr.size(),
+            Statement::Data(ref values) => values.len(),
+        };
+        statements.push((stmt, lineno));
+    }
+
+    let mut program = vec![];
+    for (stmt, lineno) in statements {
+        let values = match stmt {
+            Statement::Instr(instr, values) => {
+                program.push(instr.encode());
+                values
+            }
+            Statement::Data(values) => values,
+        };
+        for value in values {
+            program.push(match value {
+                Value::Number(n) => n,
+                Value::Label(name) => match labels.get(&name) {
+                    Some(&addr) => addr as i64,
+                    None => {
+                        return Err(AsmError {
+                            kind: AsmErrorKind::UndefinedLabel(name),
+                            line: lineno,
+                        })
+                    }
+                },
+            });
+        }
+    }
+
+    Ok(program)
+}
+
+// Format a program the way `IntComputer::from_str` expects it
+pub fn to_program_text(program: &[i64]) -> String {
+    let cells: Vec<String> = program.iter().map(|v| v.to_string()).collect();
+    cells.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IntComputer, Status};
+
+    #[test]
+    fn assemble_test() {
+        let source = "
+            ; echo doubled input until a zero is read
+            loop:   in    [value]
+                    jz    [value], done
+                    mul   [value], 2, [rb+100]
+                    out   [rb+100]
+                    jnz   1, loop
+            done:   hlt
+            value:  .data 0
+        ";
+        let program = assemble(source).unwrap();
+        assert_eq!(program[..5], [3, 15, 1006, 15, 14]);
+        assert_eq!(program[5..11], [21002, 15, 2, 100, 204, 100]);
+        assert_eq!(program.len(), 16);
+
+        let mut intcomp: IntComputer = to_program_text(&program).parse().unwrap();
+        intcomp.input.extend(&[4, -7, 0]);
+        assert_eq!(intcomp.run_until_blocked().unwrap(), Status::Halted);
+        assert_eq!(intcomp.output, [8, -14]);
+    }
+
+    #[test]
+    fn directive_test() {
+        let program = assemble("arb [rb-2]\nx: .zero 2\n.data 1, -5, x").unwrap();
+        assert_eq!(program, [209, -2, 0, 0, 1, -5, 2]);
+
+        let program = assemble("out [rb-9223372036854775808]\nout [rb + x]\nx: hlt").unwrap();
+        assert_eq!(program, [204, i64::MIN, 204, 4, 99]);
+    }
+
+    #[test]
+    fn error_test() {
+        let err = assemble("add 1, 2, 3").unwrap_err();
+        assert_eq!(err.kind, AsmErrorKind::ImmediateDestination);
+
+        let err = assemble("hlt\nfoo [1]").unwrap_err();
+        assert_eq!(err.kind, AsmErrorKind::UnknownMnemonic("foo".to_string()));
+        assert_eq!(err.line, 2);
+
+        let err = assemble("out 1, 2").unwrap_err();
+        assert_eq!(
+            err.kind,
+            AsmErrorKind::WrongOperandCount {
+                expected: 1,
+                found: 2
+            }
+        );
+
+        let err = assemble("jnz 1, nowhere").unwrap_err();
+        assert_eq!(
+            err.kind,
+            AsmErrorKind::UndefinedLabel("nowhere".to_string())
+        );
+
+        let err = assemble("a: hlt\na: hlt").unwrap_err();
+        assert_eq!(err.kind, AsmErrorKind::DuplicateLabel("a".to_string()));
+
+        let err = assemble("out [rb*2]").unwrap_err();
+        assert_eq!(err.kind, AsmErrorKind::InvalidOperand("[rb*2]".to_string()));
+
+        for op in &["[rb--5]", "[rb+-5]", "[rb-+5]", "[rb--9223372036854775808]"] {
+            let err = assemble(&format!("out {}", op)).unwrap_err();
+            assert_eq!(err.kind, AsmErrorKind::InvalidOperand(op.to_string()));
+        }
+        let err = assemble("out [rb+9223372036854775808]").unwrap_err();
+        assert!(matches!(err.kind, AsmErrorKind::InvalidOperand(_)));
+
+        let err = assemble(".zero 100000000000").unwrap_err();
+        assert_eq!(err.kind, AsmErrorKind::ZeroTooLarge(100000000000));
+    }
+}
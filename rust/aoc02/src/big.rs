@@ -0,0 +1,168 @@
+use std::str::FromStr;
+
+use crate::bigint::BigInt;
+use crate::parse::parse_values;
+use crate::{Arithmetic, Cell, IntComputer, ParseError};
+
+// Arbitrary precision cells, for programs whose values do not fit in an
+// i64. Addresses, the relative base and opcodes still have to, and going
+// past that is an `Overflow` like it is with i64 cells. `add` and `mul`
+// cannot overflow, so the arithmetic setting makes no difference.
+pub type BigComputer = IntComputer<BigInt>;
+
+static ZERO: BigInt = BigInt::ZERO;
+
+impl Cell for BigInt {
+    fn zero() -> &'static BigInt {
+        &ZERO
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        BigInt::to_i64(self)
+    }
+
+    fn add(&self, other: &BigInt, _: Arithmetic) -> Option<BigInt> {
+        Some(self + other)
+    }
+
+    fn mul(&self, other: &BigInt, _: Arithmetic) -> Option<BigInt> {
+        Some(self * other)
+    }
+}
+
+// The same machine with every cell widened, keeping its limits and the
+// steps taken so far
+impl From<&IntComputer> for BigComputer {
+    fn from(intcomp: &IntComputer) -> BigComputer {
+        let mut big = BigComputer::new(vec![]);
+        for (addr, value) in intcomp.memory.iter() {
+            // the limit is only set afterwards, so this cannot fail
+            big.memory.set(addr, BigInt::from(value)).unwrap();
+        }
+        big.memory.set_limit(intcomp.memory.limit());
+        big.cursor = intcomp.cursor;
+        big.relative_base = intcomp.relative_base;
+        big.input = intcomp.input.iter().map(|&v| BigInt::from(v)).collect();
+        big.output = intcomp.output.iter().map(|&v| BigInt::from(v)).collect();
+        big.limits = intcomp.limits;
+        big.arithmetic = intcomp.arithmetic;
+        big.steps = intcomp.steps;
+        big
+    }
+}
+
+impl FromStr for BigComputer {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> ::std::result::Result<BigComputer, ParseError> {
+        Ok(BigComputer::new(parse_values(input)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IntcodeError, Status};
+
+    // squares the value at 14 as many times as the counter at 15 says,
+    // then prints it
+    const SQUARES: &str = "2,14,14,14,1001,15,-1,15,1005,15,0,4,14,99,256,4";
+
+    #[test]
+    fn overflow_test() {
+        let mut intcomp: IntComputer = SQUARES.parse().unwrap();
+        assert_eq!(
+            intcomp.run(),
+            Err(IntcodeError::Overflow { addr: 0, op: 2 })
+        );
+        // 2^16 and 2^32 fit, 2^64 does not
+        assert_eq!(intcomp.memory[14], 1 << 32);
+
+        intcomp.arithmetic = Arithmetic::Wrapping;
+        assert_eq!(intcomp.run(), Ok(Status::ProducedOutput));
+        assert_eq!(intcomp.pop_output(), Some(0));
+    }
+
+    #[test]
+    fn big_test() {
+        let intcomp: IntComputer = SQUARES.parse().unwrap();
+        let mut big = BigComputer::from(&intcomp);
+        assert_eq!(big.run(), Ok(Status::ProducedOutput));
+        assert_eq!(
+            big.pop_output().unwrap().to_string(),
+            "340282366920938463463374607431768211456"
+        );
+        assert_eq!(big.run(), Ok(Status::Halted));
+
+        // huge literals in the program text
+        let mut big: BigComputer = "1,7,8,0,4,0,99,90000000000000000000,10000000000000000000"
+            .parse()
+            .unwrap();
+        assert_eq!(big.run(), Ok(Status::ProducedOutput));
+        assert_eq!(
+            big.pop_output().unwrap().to_string(),
+            "100000000000000000000"
+        );
+    }
+
+    #[test]
+    fn big_address_test() {
+        // addresses have to fit in an i64
+        let mut big: BigComputer = "4,100000000000000000000,99".parse().unwrap();
+        assert_eq!(big.run(), Err(IntcodeError::Overflow { addr: 0, op: 4 }));
+
+        let mut big: BigComputer = "109,-5,204,0,99".parse().unwrap();
+        assert_eq!(
+            big.run(),
+            Err(IntcodeError::NegativeAddress {
+                addr: 2,
+                op: 204,
+                target: -5
+            })
+        );
+    }
+
+    #[test]
+    fn big_limits_test() {
+        let mut intcomp: IntComputer = SQUARES.parse().unwrap();
+        intcomp.limits.max_steps = Some(4);
+        intcomp.memory.set_limit(Some(16));
+        let mut big = BigComputer::from(&intcomp);
+        assert_eq!(
+            big.run(),
+            Err(IntcodeError::StepLimitExceeded {
+                addr: 4,
+                op: 1001,
+                steps: 4
+            })
+        );
+
+        let mut big: BigComputer = "1101,1,2,100,99".parse().unwrap();
+        big.memory.set_limit(Some(10));
+        assert_eq!(
+            big.run(),
+            Err(IntcodeError::AddressOutOfRange {
+                addr: 0,
+                op: 1101,
+                target: 100,
+                limit: 10
+            })
+        );
+
+        let mut big: BigComputer = "1105,1,0".parse().unwrap();
+        big.limits.detect_loops = true;
+        assert!(matches!(
+            big.run(),
+            Err(IntcodeError::LoopDetected { period: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn big_trace_test() {
+        let mut big: BigComputer = "1002,5,10000000000,5,99,10000000000".parse().unwrap();
+        let mut entries = vec![];
+        assert_eq!(big.run_traced(&mut entries), Ok(Status::Halted));
+        let write = entries[0].write.clone().unwrap();
+        assert_eq!(write.new.to_string(), "100000000000000000000");
+    }
+}
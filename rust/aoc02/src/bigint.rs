@@ -0,0 +1,323 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::str::FromStr;
+
+// Signed arbitrary precision integer, stored as a sign and a magnitude in
+// little endian base 2^32 limbs. The magnitude never has trailing zero limbs
+// and zero is never negative, so the derived equality is value equality.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+fn trim(limbs: &mut Vec<u32>) {
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut out = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, &x) in long.iter().enumerate() {
+        let sum = x as u64 + short.get(i).copied().unwrap_or(0) as u64 + carry;
+        out.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        out.push(carry as u32);
+    }
+    out
+}
+
+// Requires |a| >= |b|
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &x) in a.iter().enumerate() {
+        let mut diff = x as i64 - b.get(i).copied().unwrap_or(0) as i64 - borrow;
+        borrow = 0;
+        if diff < 0 {
+            diff += 1 << 32;
+            borrow = 1;
+        }
+        out.push(diff as u32);
+    }
+    trim(&mut out);
+    out
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let cur = out[i + j] as u64 + x as u64 * y as u64 + carry;
+            out[i + j] = cur as u32;
+            carry = cur >> 32;
+        }
+        out[i + b.len()] = carry as u32;
+    }
+    trim(&mut out);
+    out
+}
+
+impl BigInt {
+    pub const ZERO: BigInt = BigInt {
+        negative: false,
+        limbs: Vec::new(),
+    };
+
+    fn from_parts(negative: bool, mut limbs: Vec<u32>) -> BigInt {
+        trim(&mut limbs);
+        let negative = negative && !limbs.is_empty();
+        BigInt { negative, limbs }
+    }
+
+    pub fn zero() -> BigInt {
+        BigInt::default()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.limbs.len() > 2 {
+            return None;
+        }
+        let mag = self
+            .limbs
+            .iter()
+            .rev()
+            .fold(0u64, |acc, &l| (acc << 32) | l as u64);
+        if self.negative {
+            match mag.cmp(&(1 << 63)) {
+                Ordering::Less => Some(-(mag as i64)),
+                Ordering::Equal => Some(i64::MIN),
+                Ordering::Greater => None,
+            }
+        } else {
+            i64::try_from(mag).ok()
+        }
+    }
+
+    // Divide the magnitude by a small divisor in place, returning the
+    // remainder
+    fn div_small(limbs: &mut Vec<u32>, divisor: u32) -> u32 {
+        let mut rem = 0u64;
+        for limb in limbs.iter_mut().rev() {
+            let cur = (rem << 32) | *limb as u64;
+            *limb = (cur / divisor as u64) as u32;
+            rem = cur % divisor as u64;
+        }
+        trim(limbs);
+        rem as u32
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> BigInt {
+        let mag = value.unsigned_abs();
+        BigInt::from_parts(value < 0, vec![mag as u32, (mag >> 32) as u32])
+    }
+}
+
+impl<'a> Add<&'a BigInt> for &'a BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_parts(self.negative, add_mag(&self.limbs, &other.limbs));
+        }
+        match cmp_mag(&self.limbs, &other.limbs) {
+            Ordering::Less => {
+                BigInt::from_parts(other.negative, sub_mag(&other.limbs, &self.limbs))
+            }
+            _ => BigInt::from_parts(self.negative, sub_mag(&self.limbs, &other.limbs)),
+        }
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::from_parts(!self.negative, self.limbs.clone())
+    }
+}
+
+impl<'a> Sub<&'a BigInt> for &'a BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl<'a> Mul<&'a BigInt> for &'a BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::from_parts(
+            self.negative != other.negative,
+            mul_mag(&self.limbs, &other.limbs),
+        )
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.limbs, &other.limbs),
+            (true, true) => cmp_mag(&other.limbs, &self.limbs),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // peel off nine decimal digits at a time
+        let mut limbs = self.limbs.clone();
+        let mut chunks = vec![];
+        while !limbs.is_empty() {
+            chunks.push(BigInt::div_small(&mut limbs, 1_000_000_000));
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", chunks.pop().unwrap())?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseBigIntError;
+
+impl fmt::Display for ParseBigIntError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid integer")
+    }
+}
+
+impl std::error::Error for ParseBigIntError {}
+
+impl FromStr for BigInt {
+    type Err = ParseBigIntError;
+
+    fn from_str(text: &str) -> Result<BigInt, ParseBigIntError> {
+        let (negative, digits) = match text.as_bytes().first() {
+            Some(b'-') => (true, &text[1..]),
+            Some(b'+') => (false, &text[1..]),
+            _ => (false, text),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseBigIntError);
+        }
+
+        let mut value = BigInt::zero();
+        for chunk in digits.as_bytes().chunks(9) {
+            let chunk = std::str::from_utf8(chunk).unwrap();
+            let scale = BigInt::from(10i64.pow(chunk.len() as u32));
+            let n: i64 = chunk.parse().unwrap();
+            value = &(&value * &scale) + &BigInt::from(n);
+        }
+        value.negative = negative && !value.is_zero();
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(text: &str) -> BigInt {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn convert_test() {
+        for &v in &[0, 1, -1, 42, 1 << 40, i64::MAX, i64::MIN] {
+            let b = BigInt::from(v);
+            assert_eq!(b.to_i64(), Some(v));
+            assert_eq!(b.to_string(), v.to_string());
+            assert_eq!(big(&v.to_string()), b);
+        }
+        assert_eq!(big("-0"), BigInt::zero());
+        assert_eq!(big("9223372036854775808").to_i64(), None);
+        assert_eq!(big("-9223372036854775809").to_i64(), None);
+        assert!("".parse::<BigInt>().is_err());
+        assert!("12a".parse::<BigInt>().is_err());
+    }
+
+    #[test]
+    fn arithmetic_test() {
+        let a = big("123456789012345678901234567890");
+        let b = big("-987654321098765432109876543210");
+        assert_eq!((&a + &b).to_string(), "-864197532086419753208641975320");
+        assert_eq!((&a - &b).to_string(), "1111111110111111111011111111100");
+        assert_eq!(
+            (&a * &b).to_string(),
+            "-121932631137021795226185032733622923332237463801111263526900"
+        );
+        assert_eq!(&a + &-&a, BigInt::zero());
+
+        let max = BigInt::from(i64::MAX);
+        assert_eq!((&max + &BigInt::from(1)).to_string(), "9223372036854775808");
+        assert_eq!(
+            (&max * &max).to_string(),
+            "85070591730234615847396907784232501249"
+        );
+    }
+
+    #[test]
+    fn ordering_test() {
+        let mut values = [
+            big("5"),
+            big("-100000000000000000000"),
+            big("0"),
+            big("-5"),
+            big("100000000000000000000"),
+        ];
+        values.sort();
+        let text: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            text,
+            [
+                "-100000000000000000000",
+                "-5",
+                "0",
+                "5",
+                "100000000000000000000"
+            ]
+        );
+    }
+}
@@ -0,0 +1,303 @@
+use crate::{Instruction, IntComputer, IntcodeError, Mode, Opcode, Result, Status};
+
+// Addresses past this are decoded on every visit instead of being cached
+const MAX_CACHED: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Param {
+    Position(i64),
+    Immediate(i64),
+    Relative(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Add(Param, Param, Param),
+    Mul(Param, Param, Param),
+    Input(Param),
+    Output(Param),
+    JumpIfTrue(Param, Param),
+    JumpIfFalse(Param, Param),
+    LessThan(Param, Param, Param),
+    Equals(Param, Param, Param),
+    AdjustBase(Param),
+    Halt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Decoded {
+    op: Op,
+    size: usize,
+}
+
+// Execution engine that decodes each instruction once and reuses the
+// decoded form until a write lands on one of its cells. Behaves exactly like
+// `IntComputer::run`, including the errors it reports.
+#[derive(Debug, Clone)]
+pub struct CachedComputer {
+    intcomp: IntComputer,
+    cache: Vec<Option<Decoded>>,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CachedComputer {
+    pub fn new(intcomp: IntComputer) -> CachedComputer {
+        CachedComputer {
+            intcomp,
+            cache: vec![],
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn machine(&self) -> &IntComputer {
+        &self.intcomp
+    }
+
+    // Direct access to the machine drops every decoded instruction since
+    // memory may be changed behind the cache's back.
+    pub fn machine_mut(&mut self) -> &mut IntComputer {
+        self.cache.clear();
+        &mut self.intcomp
+    }
+
+    pub fn into_inner(self) -> IntComputer {
+        self.intcomp
+    }
+
+    pub fn push_input(&mut self, value: i64) {
+        self.intcomp.push_input(value);
+    }
+
+    pub fn pop_output(&mut self) -> Option<i64> {
+        self.intcomp.pop_output()
+    }
+
+    fn decode(&self, addr: usize) -> Result<Decoded> {
+        let memory = &self.intcomp.memory;
+        let instr = Instruction::decode(addr, memory[addr])?;
+        let p = |n: usize| {
+            let value = memory[addr + 1 + n];
+            match instr.modes[n] {
+                Mode::Position => Param::Position(value),
+                Mode::Immediate => Param::Immediate(value),
+                Mode::Relative => Param::Relative(value),
+            }
+        };
+
+        let op = match instr.opcode {
+            Opcode::Add => Op::Add(p(0), p(1), p(2)),
+            Opcode::Mul => Op::Mul(p(0), p(1), p(2)),
+            Opcode::Input => Op::Input(p(0)),
+            Opcode::Output => Op::Output(p(0)),
+            Opcode::JumpIfTrue => Op::JumpIfTrue(p(0), p(1)),
+            Opcode::JumpIfFalse => Op::JumpIfFalse(p(0), p(1)),
+            Opcode::LessThan => Op::LessThan(p(0), p(1), p(2)),
+            Opcode::Equals => Op::Equals(p(0), p(1), p(2)),
+            Opcode::AdjustBase => Op::AdjustBase(p(0)),
+            Opcode::Halt => Op::Halt,
+        };
+        Ok(Decoded {
+            op,
+            size: instr.size(),
+        })
+    }
+
+    fn fetch(&mut self) -> Result<Decoded> {
+        let cursor = self.intcomp.cursor;
+        if cursor >= MAX_CACHED {
+            return self.decode(cursor);
+        }
+        if let Some(Some(decoded)) = self.cache.get(cursor) {
+            self.hits += 1;
+            return Ok(*decoded);
+        }
+
+        self.misses += 1;
+        let decoded = self.decode(cursor)?;
+        if cursor >= self.cache.len() {
+            self.cache.resize(cursor + 1, None);
+        }
+        self.cache[cursor] = Some(decoded);
+        Ok(decoded)
+    }
+
+    // Drop any decoded instruction that covers `addr`
+    fn invalidate(&mut self, addr: usize) {
+        let end = addr.min(self.cache.len().saturating_sub(1));
+        for start in addr.saturating_sub(3)..=end {
+            if let Some(d) = self.cache[start] {
+                if start + d.size > addr {
+                    self.cache[start] = None;
+                }
+            }
+        }
+    }
+
+    fn addr(&self, p: Param) -> Result<usize> {
+        match p {
+            Param::Position(a) => self.intcomp.check_addr(a),
+            Param::Relative(a) => {
+                let target = self.intcomp.add(self.intcomp.relative_base, a)?;
+                self.intcomp.check_addr(target)
+            }
+            Param::Immediate(_) => unreachable!(),
+        }
+    }
+
+    fn read(&self, p: Param) -> Result<i64> {
+        match p {
+            Param::Immediate(v) => Ok(v),
+            _ => Ok(self.intcomp.memory[self.addr(p)?]),
+        }
+    }
+
+    fn write(&mut self, p: Param, n: usize, value: i64) -> Result<()> {
+        if let Param::Immediate(_) = p {
+            return Err(IntcodeError::InvalidMode {
+                addr: self.intcomp.cursor,
+                op: self.intcomp.get_op(),
+                param: n,
+                mode: 1,
+            });
+        }
+        let addr = self.addr(p)?;
+        self.intcomp.store(addr, value)?;
+        if !self.cache.is_empty() {
+            self.invalidate(addr);
+        }
+        Ok(())
+    }
+
+    pub fn execute_instr(&mut self) -> Result<Option<Status>> {
+        let decoded = self.fetch()?;
+        let mut next = self.intcomp.cursor + decoded.size;
+        let mut status = None;
+
+        match decoded.op {
+            Op::Add(a, b, c) => {
+                let v = self.intcomp.add(self.read(a)?, self.read(b)?)?;
+                self.write(c, 2, v)?;
+            }
+            Op::Mul(a, b, c) => {
+                let v = self.intcomp.mul(self.read(a)?, self.read(b)?)?;
+                self.write(c, 2, v)?;
+            }
+            Op::Input(a) => {
+                let v = match self.intcomp.input.pop_front() {
+                    Some(v) => v,
+                    None => return Ok(Some(Status::NeedsInput)),
+                };
+                self.write(a, 0, v)?;
+            }
+            Op::Output(a) => {
+                let v = self.read(a)?;
+                self.intcomp.output.push_back(v);
+                status = Some(Status::ProducedOutput);
+            }
+            Op::JumpIfTrue(a, b) | Op::JumpIfFalse(a, b) => {
+                let cond = self.read(a)? != 0;
+                if cond == matches!(decoded.op, Op::JumpIfTrue(..)) {
+                    let target = self.read(b)?;
+                    next = self.intcomp.check_addr(target)?;
+                }
+            }
+            Op::LessThan(a, b, c) => {
+                let v = self.read(a)? < self.read(b)?;
+                self.write(c, 2, v as i64)?;
+            }
+            Op::Equals(a, b, c) => {
+                let v = self.read(a)? == self.read(b)?;
+                self.write(c, 2, v as i64)?;
+            }
+            Op::AdjustBase(a) => {
+                let base = self.intcomp.relative_base;
+                self.intcomp.relative_base = self.intcomp.add(base, self.read(a)?)?;
+            }
+            Op::Halt => return Ok(Some(Status::Halted)),
+        }
+
+        self.intcomp.cursor = next;
+        Ok(status)
+    }
+
+    pub fn run(&mut self) -> Result<Status> {
+        loop {
+            let op = self.intcomp.get_op();
+            self.intcomp.check_steps()?;
+            let status = self.execute_instr()?;
+            if status == Some(Status::NeedsInput) {
+                return Ok(Status::NeedsInput);
+            }
+            self.intcomp.count_step(op)?;
+            if let Some(status) = status {
+                return Ok(status);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::{assemble, to_program_text};
+
+    fn run_both(prog: &str, input: &[i64]) -> (IntComputer, CachedComputer) {
+        let mut plain: IntComputer = prog.parse().unwrap();
+        plain.input.extend(input);
+        let mut cached = CachedComputer::new(plain.clone());
+
+        loop {
+            let expected = plain.run();
+            assert_eq!(cached.run(), expected);
+            if expected != Ok(Status::ProducedOutput) {
+                break;
+            }
+        }
+        assert_eq!(cached.machine(), &plain);
+        (plain, cached)
+    }
+
+    #[test]
+    fn matches_interpreter_test() {
+        run_both("1,9,10,3,2,3,11,0,99,30,40,50", &[]);
+        run_both("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9", &[0]);
+        run_both(
+            "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+            &[],
+        );
+        run_both("1,0,0,0,77", &[]);
+        run_both("11101,1,2,3,99", &[]);
+        run_both("204,-3,99", &[]);
+    }
+
+    #[test]
+    fn loop_hits_test() {
+        // count down from 1000 in a tight loop
+        let (plain, cached) = run_both("1001,10,-1,10,1005,10,0,99,0,0,1000", &[]);
+        assert_eq!(plain.memory[10], 0);
+        assert_eq!(cached.misses, 3);
+        assert_eq!(cached.hits, 1000 * 2 + 1 - 3);
+    }
+
+    #[test]
+    fn self_modifying_test() {
+        // the second pass patches the first instruction from add to mul,
+        // which must not run from a stale cache entry
+        let source = "
+            start:  add   [val], [val], [val]
+                    jnz   [done], end
+                    add   2, 0, [start]
+                    add   1, 0, [done]
+                    jnz   1, start
+            end:    hlt
+            val:    .data 3
+            done:   .data 0
+        ";
+        let program = to_program_text(&assemble(source).unwrap());
+        let (plain, cached) = run_both(&program, &[]);
+        assert_eq!(plain.memory[19], 36);
+        assert_eq!(cached.misses, 7);
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+use crate::disasm::{decode_at, item_text, Item, Line};
+use crate::{Mode, Opcode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    Fallthrough,
+    Jump,
+    // the target is read from memory and only known at run time
+    Indirect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub from: usize,
+    // None for indirect jumps
+    pub to: Option<usize>,
+    pub kind: EdgeKind,
+}
+
+// Straight-line run of instructions. A block that ends in `.data` stops at a
+// cell that does not decode, where the program would fault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    pub lines: Vec<Line>,
+}
+
+impl Block {
+    pub fn end(&self) -> usize {
+        self.lines.last().map_or(self.start, |l| l.addr + l.size())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg {
+    pub blocks: BTreeMap<usize, Block>,
+    pub edges: Vec<Edge>,
+}
+
+// Where control can go after `line`: whether it falls through, and the jump
+// target if it may jump (None inside for an indirect jump).
+fn successors(line: &Line) -> (bool, Option<Option<usize>>) {
+    let (instr, operands) = match line.item {
+        Item::Instr(instr, ref operands) => (instr, operands),
+        Item::Data(_) => return (false, None),
+    };
+    if instr.opcode == Opcode::Halt {
+        return (false, None);
+    }
+    if !instr.opcode.is_jump() {
+        return (true, None);
+    }
+
+    let target = match operands[1] {
+        t if t.mode != Mode::Immediate => Some(None),
+        // jumping to a negative or unaddressable target faults
+        t => usize::try_from(t.value).ok().map(Some),
+    };
+    match operands[0] {
+        c if c.mode == Mode::Immediate => {
+            let taken = (c.value != 0) == (instr.opcode == Opcode::JumpIfTrue);
+            if taken {
+                (false, target)
+            } else {
+                (true, None)
+            }
+        }
+        _ => (true, target),
+    }
+}
+
+// Recursive traversal from address 0. Only code reachable through direct
+// jumps and fallthrough is decoded; a jump into the middle of an instruction
+// starts a new block that overlaps it.
+pub fn build(program: &[i64]) -> Cfg {
+    let mut lines: BTreeMap<usize, Line> = BTreeMap::new();
+    let mut leaders = BTreeSet::new();
+    let mut work = vec![0];
+    leaders.insert(0);
+
+    while let Some(addr) = work.pop() {
+        if addr >= program.len() || lines.contains_key(&addr) {
+            continue;
+        }
+        let line = decode_at(program, addr).unwrap_or(Line {
+            addr,
+            item: Item::Data(program[addr]),
+        });
+        let next = addr + line.size();
+        let (falls, jump) = successors(&line);
+        if let Some(Some(target)) = jump {
+            leaders.insert(target);
+            work.push(target);
+        }
+        if falls {
+            if jump.is_some() {
+                leaders.insert(next);
+            }
+            work.push(next);
+        }
+        lines.insert(addr, line);
+    }
+
+    let mut blocks = BTreeMap::new();
+    let mut edges = vec![];
+    for &start in leaders.iter().filter(|a| lines.contains_key(a)) {
+        let mut block = Block {
+            start,
+            lines: vec![],
+        };
+        let mut addr = start;
+        loop {
+            let line = lines[&addr].clone();
+            let next = addr + line.size();
+            let (falls, jump) = successors(&line);
+            block.lines.push(line);
+
+            if let Some(target) = jump {
+                let kind = match target {
+                    Some(_) => EdgeKind::Jump,
+                    None => EdgeKind::Indirect,
+                };
+                edges.push(Edge {
+                    from: start,
+                    to: target,
+                    kind,
+                });
+            }
+            let next_known = lines.contains_key(&next);
+            if falls && next_known && (jump.is_some() || leaders.contains(&next)) {
+                edges.push(Edge {
+                    from: start,
+                    to: Some(next),
+                    kind: EdgeKind::Fallthrough,
+                });
+            }
+            if !falls || !next_known || jump.is_some() || leaders.contains(&next) {
+                break;
+            }
+            addr = next;
+        }
+        blocks.insert(start, block);
+    }
+
+    Cfg { blocks, edges }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Cfg {
+    // Graphviz DOT, one box per block listing its instructions. Indirect
+    // jumps all point at a single `unknown` node, and jumps to addresses
+    // outside the decoded program get a node of their own.
+    pub fn to_dot(&self) -> String {
+        let no_labels = BTreeMap::new();
+        let mut out = String::new();
+        writeln!(out, "digraph cfg {{").unwrap();
+        writeln!(out, "    node [shape=box, fontname=monospace];").unwrap();
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for line in &block.lines {
+                let text = item_text(&line.item, &no_labels);
+                label.push_str(&format!("{:>6}  {}\\l", line.addr, escape(&text)));
+            }
+            writeln!(out, "    b{} [label=\"{}\"];", block.start, label).unwrap();
+        }
+
+        let mut outside = BTreeSet::new();
+        if self.edges.iter().any(|e| e.kind == EdgeKind::Indirect) {
+            writeln!(out, "    unknown [shape=diamond, label=\"?\"];").unwrap();
+        }
+        for edge in &self.edges {
+            let to = match edge.to {
+                Some(addr) if self.blocks.contains_key(&addr) => format!("b{}", addr),
+                Some(addr) => {
+                    outside.insert(addr);
+                    format!("x{}", addr)
+                }
+                None => "unknown".to_string(),
+            };
+            let style = match edge.kind {
+                EdgeKind::Fallthrough => "",
+                EdgeKind::Jump => " [label=\"jump\"]",
+                EdgeKind::Indirect => " [style=dashed]",
+            };
+            writeln!(out, "    b{} -> {}{};", edge.from, to, style).unwrap();
+        }
+        for addr in outside {
+            writeln!(out, "    x{} [shape=plaintext, label=\"{}?\"];", addr, addr).unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    fn graph(text: &str) -> Cfg {
+        build(&parse_program(text).unwrap())
+    }
+
+    fn spans(cfg: &Cfg) -> Vec<(usize, usize)> {
+        cfg.blocks.values().map(|b| (b.start, b.end())).collect()
+    }
+
+    #[test]
+    fn straight_line_test() {
+        let cfg = graph("1,9,10,3,2,3,11,0,99,30,40,50");
+        assert_eq!(spans(&cfg), [(0, 9)]);
+        assert_eq!(cfg.edges, []);
+    }
+
+    #[test]
+    fn loop_test() {
+        let cfg = graph("1001,10,-1,10,1005,10,0,99,0,0,5");
+        assert_eq!(spans(&cfg), [(0, 7), (7, 8)]);
+        assert_eq!(
+            cfg.edges,
+            [
+                Edge {
+                    from: 0,
+                    to: Some(0),
+                    kind: EdgeKind::Jump
+                },
+                Edge {
+                    from: 0,
+                    to: Some(7),
+                    kind: EdgeKind::Fallthrough
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn indirect_test() {
+        // jnz [7], [8] falls through to the halt or goes somewhere unknown
+        let cfg = graph("5,7,8,99,0,0,0,1,3");
+        assert_eq!(spans(&cfg), [(0, 3), (3, 4)]);
+        assert_eq!(cfg.edges[0].kind, EdgeKind::Indirect);
+        assert_eq!(cfg.edges[0].to, None);
+
+        // an unconditional jump skips the cell at 3, and the target does
+        // not decode
+        let cfg = graph("1105,1,4,77,77");
+        assert_eq!(spans(&cfg), [(0, 3), (4, 5)]);
+        assert_eq!(cfg.blocks[&4].lines[0].item, Item::Data(77));
+        assert_eq!(cfg.edges.len(), 1);
+    }
+
+    #[test]
+    fn dot_test() {
+        let dot = graph("5,7,8,99,0,0,0,1,3").to_dot();
+        assert!(dot.starts_with("digraph cfg {\n"));
+        assert!(dot.contains("    b0 [label=\"     0  jnz   [7], [8]\\l\"];\n"));
+        assert!(dot.contains("    b0 -> unknown [style=dashed];\n"));
+        assert!(dot.contains("    b0 -> b3;\n"));
+
+        let dot = graph("1105,1,20,99").to_dot();
+        assert!(dot.contains("    b0 -> x20 [label=\"jump\"];\n"));
+        assert!(dot.contains("    x20 [shape=plaintext, label=\"20?\"];\n"));
+    }
+}
@@ -0,0 +1,436 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufRead, Write};
+
+use crate::disasm::disassemble_one;
+use crate::history::History;
+use crate::snapshot::Snapshot;
+use crate::{IntComputer, IntcodeError, Status};
+
+const HELP: &str = "\
+commands:
+  s, step [n]          execute n instructions (default 1)
+  c, continue          run until a breakpoint, watchpoint, halt, input or
+                       a limit is hit
+  b, break <addr>      set a breakpoint
+  d, delete <addr>     remove a breakpoint
+  w, watch <addr>      stop when the cell at addr changes
+  u, unwatch <addr>    remove a watchpoint
+  r, regs              show cursor, relative base and pending i/o
+  x <addr> [n]         show n memory cells starting at addr
+  poke <addr> <value>  write value to memory
+  limit [n]            stop after n steps in total (no argument: no limit)
+  loops on|off         stop when the machine is stuck in a loop (default on)
+  i, input <v>...      queue input values
+  save <file>          write a snapshot of the machine to file
+  load <file>          restore the machine from a snapshot file
+  l, list [n]          disassemble n instructions from the cursor
+  record [interval]    keep history for stepping backwards, with a
+                       checkpoint every interval steps (default 1000)
+  rs, rstep [n]        step n instructions backwards (default 1)
+  rw <addr>            go back to just before the last write to addr
+  rewind <n>           go back to step n
+  q, quit              leave the debugger
+an empty line repeats the last command";
+
+const CHECKPOINT_INTERVAL: u64 = 1000;
+const MAX_CHECKPOINTS: usize = 100;
+
+// Why stepping stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Stepped,
+    Breakpoint(usize),
+    Watchpoint { addr: usize, old: i64, new: i64 },
+    Status(Status),
+    Fault(IntcodeError),
+}
+
+pub struct Debugger {
+    pub intcomp: IntComputer,
+    pub breakpoints: BTreeSet<usize>,
+    // last value seen in each watched cell
+    watchpoints: BTreeMap<usize, i64>,
+    pub steps: u64,
+    // recorded when stepping backwards is enabled
+    pub history: Option<History>,
+}
+
+impl Debugger {
+    // Loop detection is turned on, so continuing into a stuck program comes
+    // back instead of hanging
+    pub fn new(mut intcomp: IntComputer) -> Debugger {
+        intcomp.limits.detect_loops = true;
+        Debugger {
+            intcomp,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            steps: 0,
+            history: None,
+        }
+    }
+
+    pub fn record(&mut self, interval: u64) {
+        self.history = Some(History::new(self.steps, interval, MAX_CHECKPOINTS));
+    }
+
+    // Changes made by hand are not in the history, so it starts over
+    fn restart_history(&mut self) {
+        if let Some(ref history) = self.history {
+            let interval = history.interval();
+            self.record(interval);
+        }
+    }
+
+    pub fn watch(&mut self, addr: usize) {
+        let value = self.intcomp.memory[addr];
+        self.watchpoints.insert(addr, value);
+    }
+
+    pub fn unwatch(&mut self, addr: usize) -> bool {
+        self.watchpoints.remove(&addr).is_some()
+    }
+
+    fn check_watchpoints(&mut self) -> Option<Stop> {
+        let memory = &self.intcomp.memory;
+        for (&addr, seen) in self.watchpoints.iter_mut() {
+            let new = memory[addr];
+            if new != *seen {
+                let old = *seen;
+                *seen = new;
+                return Some(Stop::Watchpoint { addr, old, new });
+            }
+        }
+        None
+    }
+
+    // Steps are counted and checked against the machine's limits the same
+    // way `run` does it
+    pub fn step(&mut self) -> Stop {
+        let op = self.intcomp.get_op();
+        if let Err(e) = self.intcomp.check_steps() {
+            return Stop::Fault(e);
+        }
+        let result = match self.history {
+            Some(ref mut history) => self.intcomp.execute_recorded(history),
+            None => self.intcomp.execute_instr(),
+        };
+        match result {
+            Err(e) => return Stop::Fault(e),
+            Ok(Some(Status::ProducedOutput)) | Ok(None) => self.steps += 1,
+            Ok(Some(status)) => return Stop::Status(status),
+        }
+        if let Err(e) = self.intcomp.count_step(op) {
+            return Stop::Fault(e);
+        }
+        self.check_watchpoints().unwrap_or(Stop::Stepped)
+    }
+
+    // Move backwards with `rewind`, which returns false if it ran out of
+    // history. Watchpoints take the values at the new position, and output
+    // that was already shown stays shown.
+    fn back<F>(&mut self, rewind: F, out: &mut dyn Write) -> io::Result<()>
+    where
+        F: FnOnce(&mut IntComputer, &mut History) -> crate::Result<bool>,
+    {
+        let history = match self.history {
+            Some(ref mut history) => history,
+            None => return writeln!(out, "not recording, use record first"),
+        };
+        let result = rewind(&mut self.intcomp, history);
+        self.steps = history.step();
+        for (&addr, seen) in self.watchpoints.iter_mut() {
+            *seen = self.intcomp.memory[addr];
+        }
+        match result {
+            Ok(true) => (),
+            Ok(false) => writeln!(out, "reached the start of recorded history")?,
+            Err(e) => writeln!(out, "fault: {}", e)?,
+        }
+        self.list(1, out)
+    }
+
+    pub fn cont(&mut self) -> Stop {
+        loop {
+            match self.step() {
+                Stop::Stepped if self.breakpoints.contains(&self.intcomp.cursor) => {
+                    return Stop::Breakpoint(self.intcomp.cursor)
+                }
+                Stop::Stepped => continue,
+                stop => return stop,
+            }
+        }
+    }
+
+    fn report<W: Write>(&mut self, stop: Stop, out: &mut W) -> io::Result<()> {
+        while let Some(v) = self.intcomp.pop_output() {
+            writeln!(out, "output: {}", v)?;
+        }
+        match stop {
+            Stop::Stepped => (),
+            Stop::Breakpoint(addr) => writeln!(out, "breakpoint at {}", addr)?,
+            Stop::Watchpoint { addr, old, new } => {
+                writeln!(out, "watchpoint [{}]: {} -> {}", addr, old, new)?
+            }
+            Stop::Status(Status::Halted) => writeln!(out, "halted")?,
+            Stop::Status(status) => writeln!(out, "stopped: {:?}", status)?,
+            Stop::Fault(e) => writeln!(out, "fault: {}", e)?,
+        }
+        self.list(1, out)
+    }
+
+    fn list<W: Write + ?Sized>(&self, count: usize, out: &mut W) -> io::Result<()> {
+        let mut addr = self.intcomp.cursor;
+        for _ in 0..count {
+            let (text, size) = disassemble_one(&self.intcomp.memory, addr);
+            let marker = if self.breakpoints.contains(&addr) {
+                '*'
+            } else {
+                ' '
+            };
+            writeln!(out, "{}{:>6}    {}", marker, addr, text)?;
+            addr += size;
+        }
+        Ok(())
+    }
+
+    // Execute one command line, returns false once the user quits
+    pub fn command<W: Write>(&mut self, line: &str, out: &mut W) -> io::Result<bool> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (cmd, args) = match words.split_first() {
+            Some((cmd, args)) => (*cmd, args),
+            None => return Ok(true),
+        };
+        match (cmd, args) {
+            ("loops", [flag @ ("on" | "off")]) => {
+                self.intcomp.limits.detect_loops = *flag == "on";
+                return Ok(true);
+            }
+            ("save", [path]) => {
+                if let Err(e) = self.intcomp.snapshot().save_to_file(path) {
+                    writeln!(out, "save failed: {}", e)?;
+                }
+                return Ok(true);
+            }
+            ("load", [path]) => {
+                match Snapshot::load_from_file(path) {
+                    Ok(snapshot) => {
+                        self.intcomp.restore(&snapshot);
+                        self.restart_history();
+                    }
+                    Err(e) => writeln!(out, "load failed: {}", e)?,
+                }
+                return Ok(true);
+            }
+            _ => (),
+        }
+        let nums: Vec<i64> = match args.iter().map(|a| a.parse()).collect() {
+            Ok(nums) => nums,
+            Err(_) => {
+                writeln!(out, "invalid number in {:?}", line)?;
+                return Ok(true);
+            }
+        };
+        let addr = |n: usize| nums.get(n).filter(|&&v| v >= 0).map(|&v| v as usize);
+
+        match (cmd, addr(0)) {
+            ("s", _) | ("step", _) => {
+                let mut stop = Stop::Stepped;
+                for _ in 0..addr(0).unwrap_or(1) {
+                    stop = self.step();
+                    if stop != Stop::Stepped {
+                        break;
+                    }
+                }
+                self.report(stop, out)?;
+            }
+            ("c", _) | ("continue", _) => {
+                let stop = self.cont();
+                self.report(stop, out)?;
+            }
+            ("b", Some(a)) | ("break", Some(a)) => {
+                self.breakpoints.insert(a);
+            }
+            ("d", Some(a)) | ("delete", Some(a)) => {
+                if !self.breakpoints.remove(&a) {
+                    writeln!(out, "no breakpoint at {}", a)?;
+                }
+            }
+            ("w", Some(a)) | ("watch", Some(a)) => self.watch(a),
+            ("u", Some(a)) | ("unwatch", Some(a)) => {
+                if !self.unwatch(a) {
+                    writeln!(out, "no watchpoint at {}", a)?;
+                }
+            }
+            ("r", _) | ("regs", _) => {
+                let intcomp = &self.intcomp;
+                writeln!(out, "cursor:        {}", intcomp.cursor)?;
+                writeln!(out, "relative base: {}", intcomp.relative_base)?;
+                writeln!(out, "steps:         {}", self.steps)?;
+                writeln!(out, "input:         {:?}", intcomp.input)?;
+                writeln!(out, "output:        {:?}", intcomp.output)?;
+            }
+            ("x", Some(a)) => {
+                let count = addr(1).unwrap_or(1);
+                for a in a..a + count {
+                    writeln!(out, "[{}] = {}", a, self.intcomp.memory[a])?;
+                }
+            }
+            ("poke", Some(a)) if nums.len() == 2 => {
+                if let Err(e) = self.intcomp.memory.set(a, nums[1]) {
+                    writeln!(out, "{}", e)?;
+                }
+                self.restart_history();
+            }
+            ("limit", _) => self.intcomp.limits.max_steps = addr(0).map(|n| n as u64),
+            ("record", _) => self.record(addr(0).unwrap_or(CHECKPOINT_INTERVAL as usize) as u64),
+            ("rs", _) | ("rstep", _) => {
+                let count = addr(0).unwrap_or(1);
+                self.back(
+                    |intcomp, history| {
+                        for _ in 0..count {
+                            if !intcomp.step_back(history)? {
+                                return Ok(false);
+                            }
+                        }
+                        Ok(true)
+                    },
+                    out,
+                )?;
+            }
+            ("rw", Some(a)) => self.back(
+                |intcomp, history| Ok(intcomp.run_back_to_write(history, a)?.is_some()),
+                out,
+            )?,
+            ("rewind", Some(n)) => {
+                self.back(|intcomp, history| intcomp.rewind_to(history, n as u64), out)?
+            }
+            ("i", _) | ("input", _) => self.intcomp.input.extend(&nums),
+            ("l", _) | ("list", _) => self.list(addr(0).unwrap_or(10), out)?,
+            ("q", _) | ("quit", _) => return Ok(false),
+            ("h", _) | ("help", _) => writeln!(out, "{}", HELP)?,
+            _ => writeln!(out, "unknown command {:?}, try help", line.trim())?,
+        }
+        Ok(true)
+    }
+
+    pub fn repl<R: BufRead, W: Write>(&mut self, input: R, mut out: W) -> io::Result<()> {
+        let mut last = String::new();
+        self.list(1, &mut out)?;
+        write!(out, "(icdb) ")?;
+        out.flush()?;
+
+        for line in input.lines() {
+            let mut line = line?;
+            if line.trim().is_empty() {
+                line = last.clone();
+            }
+            if !self.command(&line, &mut out)? {
+                break;
+            }
+            last = line;
+            write!(out, "(icdb) ")?;
+            out.flush()?;
+        }
+        writeln!(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn debugger(prog: &str) -> Debugger {
+        Debugger::new(prog.parse().unwrap())
+    }
+
+    #[test]
+    fn breakpoint_test() {
+        let mut dbg = debugger("1101,1,2,20,1101,3,4,21,2,20,21,22,99");
+        dbg.breakpoints.insert(8);
+        assert_eq!(dbg.cont(), Stop::Breakpoint(8));
+        assert_eq!(dbg.steps, 2);
+        assert_eq!(dbg.cont(), Stop::Status(Status::Halted));
+        assert_eq!(dbg.intcomp.memory[22], 21);
+    }
+
+    #[test]
+    fn watchpoint_test() {
+        let mut dbg = debugger("1101,1,2,20,1101,3,4,21,2,20,21,22,99");
+        dbg.watch(21);
+        assert_eq!(
+            dbg.cont(),
+            Stop::Watchpoint {
+                addr: 21,
+                old: 0,
+                new: 7
+            }
+        );
+        assert_eq!(dbg.intcomp.cursor, 8);
+    }
+
+    #[test]
+    fn limits_test() {
+        // bounces between 0 and 3 forever
+        let mut dbg = debugger("1105,1,3,1105,1,0");
+        assert!(matches!(
+            dbg.cont(),
+            Stop::Fault(IntcodeError::LoopDetected { period: 2, .. })
+        ));
+
+        let mut dbg = debugger("1105,1,3,1105,1,0");
+        let script = "loops off\nlimit 5\nc\ns\nlimit\nloops on\nc\nq\n";
+        let mut out = vec![];
+        dbg.repl(script.as_bytes(), &mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("fault: step limit of 5 exceeded"));
+        assert!(out.contains("fault: infinite loop between 0 and 3"));
+        assert_eq!(dbg.steps, dbg.intcomp.steps);
+    }
+
+    #[test]
+    fn reverse_test() {
+        let mut dbg = debugger("1101,1,2,20,1101,3,4,21,2,20,21,22,99");
+        let script = "rs\nrecord 2\nc\nrw 22\nx 22\nrs\nrewind 0\nr\nrs\nq\n";
+        let mut out = vec![];
+        dbg.repl(script.as_bytes(), &mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("not recording, use record first"));
+        assert!(out.contains("halted"));
+        assert!(out.contains("     8    mul   [20], [21], [22]\n(icdb) [22] = 0"));
+        assert!(out.contains("     4    add   3, 4, [21]"));
+        assert!(out.contains("steps:         0"));
+        assert!(out.contains("reached the start of recorded history"));
+        assert_eq!(dbg.intcomp.cursor, 0);
+        assert_eq!(dbg.steps, dbg.intcomp.steps);
+
+        // the step limit goes by the position after going back, whether it
+        // undoes steps or replays from a checkpoint
+        for interval in &[1000, 2] {
+            let mut dbg = debugger("1101,1,2,20,1101,3,4,21,2,20,21,22,99");
+            let script = format!("record {}\ns 3\nrs 2\nlimit 2\ns\nq\n", interval);
+            let mut out = vec![];
+            dbg.repl(script.as_bytes(), &mut out).unwrap();
+
+            let out = String::from_utf8(out).unwrap();
+            assert!(!out.contains("fault"), "{}", out);
+            assert_eq!((dbg.steps, dbg.intcomp.steps), (2, 2));
+        }
+    }
+
+    #[test]
+    fn repl_test() {
+        let mut dbg = debugger("3,9,1002,9,2,9,4,9,99,0");
+        let script = "c\ni 21\npoke 4 1\nb 6\nc\nx 9\n\nc\nq\n";
+        let mut out = vec![];
+        dbg.repl(script.as_bytes(), &mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("stopped: NeedsInput"));
+        assert!(out.contains("breakpoint at 6"));
+        assert!(out.contains("*     6    out   [9]"));
+        assert!(out.contains("[9] = 21"));
+        assert!(out.contains("output: 21\nhalted"));
+    }
+}
@@ -0,0 +1,381 @@
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, BufRead, Stdout, Write};
+#[cfg(feature = "std")]
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::{Cell, IntComputer, Result, Status};
+
+// Something attached to the machine's I/O: a screen, a joystick, another
+// machine. The input and output instructions call it directly. `input`
+// returns None when there is nothing to read yet, which makes `run_device`
+// return `Status::NeedsInput`.
+pub trait Device<C = i64> {
+    fn input(&mut self) -> Option<C>;
+    fn output(&mut self, value: C);
+}
+
+impl<C, D: Device<C> + ?Sized> Device<C> for &mut D {
+    fn input(&mut self) -> Option<C> {
+        (**self).input()
+    }
+
+    fn output(&mut self, value: C) {
+        (**self).output(value)
+    }
+}
+
+// The machine's own input and output queues, the device `run` uses
+pub(crate) struct Queues<'a, C> {
+    input: &'a mut VecDeque<C>,
+    output: &'a mut VecDeque<C>,
+}
+
+impl<'a, C> Queues<'a, C> {
+    pub(crate) fn new(input: &'a mut VecDeque<C>, output: &'a mut VecDeque<C>) -> Queues<'a, C> {
+        Queues { input, output }
+    }
+}
+
+impl<C> Device<C> for Queues<'_, C> {
+    fn input(&mut self) -> Option<C> {
+        self.input.pop_front()
+    }
+
+    fn output(&mut self, value: C) {
+        self.output.push_back(value);
+    }
+}
+
+// Reads what is already in the input queue before asking `device`
+struct Attached<'a, C> {
+    queued: &'a mut VecDeque<C>,
+    device: &'a mut dyn Device<C>,
+}
+
+impl<C> Device<C> for Attached<'_, C> {
+    fn input(&mut self) -> Option<C> {
+        self.queued.pop_front().or_else(|| self.device.input())
+    }
+
+    fn output(&mut self, value: C) {
+        self.device.output(value);
+    }
+}
+
+impl<C: Cell> IntComputer<C> {
+    // Run with `device` in place of the queues until the program halts or
+    // the device has no input, under the same limits as `run`. Values
+    // already in the input queue are read before asking the device.
+    pub fn run_device(&mut self, device: &mut dyn Device<C>) -> Result<Status> {
+        let mut queued = core::mem::take(&mut self.input);
+        let result = self.run_attached(&mut Attached {
+            queued: &mut queued,
+            device,
+        });
+        self.input = queued;
+        result
+    }
+
+    fn run_attached(&mut self, io: &mut Attached<C>) -> Result<Status> {
+        loop {
+            let op = self.get_op();
+            self.check_steps()?;
+            match self.execute_io(io)? {
+                Some(Status::NeedsInput) => return Ok(Status::NeedsInput),
+                Some(Status::Halted) => return Ok(Status::Halted),
+                _ => self.count_step(op)?,
+            }
+        }
+    }
+}
+
+// Fixed input, collected output
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Queue {
+    pub input: VecDeque<i64>,
+    pub output: Vec<i64>,
+}
+
+impl Queue {
+    pub fn new(input: &[i64]) -> Queue {
+        Queue {
+            input: input.iter().copied().collect(),
+            output: vec![],
+        }
+    }
+}
+
+impl Device for Queue {
+    fn input(&mut self) -> Option<i64> {
+        self.input.pop_front()
+    }
+
+    fn output(&mut self, value: i64) {
+        self.output.push(value);
+    }
+}
+
+// One number per line in both directions, blank lines are skipped. The
+// first I/O or parse error ends input and is kept in `error`.
+#[cfg(feature = "std")]
+pub struct Text<R, W> {
+    input: R,
+    output: W,
+    pub error: Option<io::Error>,
+}
+
+#[cfg(feature = "std")]
+impl<R: BufRead, W: Write> Text<R, W> {
+    pub fn new(input: R, output: W) -> Text<R, W> {
+        Text {
+            input,
+            output,
+            error: None,
+        }
+    }
+
+    fn read(&mut self) -> io::Result<Option<i64>> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            let text = line.trim();
+            if text.is_empty() {
+                continue;
+            }
+            return match text.parse() {
+                Ok(v) => Ok(Some(v)),
+                Err(_) => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("not a number: {:?}", text),
+                )),
+            };
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+pub fn stdio() -> Text<io::StdinLock<'static>, Stdout> {
+    Text::new(io::stdin().lock(), io::stdout())
+}
+
+#[cfg(feature = "std")]
+impl<R: BufRead, W: Write> Device for Text<R, W> {
+    fn input(&mut self) -> Option<i64> {
+        if self.error.is_some() {
+            return None;
+        }
+        match self.read() {
+            Ok(v) => v,
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+
+    fn output(&mut self, value: i64) {
+        if self.error.is_none() {
+            if let Err(e) = writeln!(self.output, "{}", value).and_then(|_| self.output.flush()) {
+                self.error = Some(e);
+            }
+        }
+    }
+}
+
+// Connects machines running on different threads. Reading blocks until a
+// value arrives and ends once every sender is gone; output to a receiver
+// that is gone is dropped.
+#[cfg(feature = "std")]
+pub struct Channel {
+    pub input: Receiver<i64>,
+    pub output: Sender<i64>,
+}
+
+#[cfg(feature = "std")]
+impl Channel {
+    pub fn new(input: Receiver<i64>, output: Sender<i64>) -> Channel {
+        Channel { input, output }
+    }
+
+    // Two ends, each reading what the other writes
+    pub fn pair() -> (Channel, Channel) {
+        let (a_tx, a_rx) = mpsc::channel();
+        let (b_tx, b_rx) = mpsc::channel();
+        (Channel::new(a_rx, b_tx), Channel::new(b_rx, a_tx))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Device for Channel {
+    fn input(&mut self) -> Option<i64> {
+        self.input.recv().ok()
+    }
+
+    fn output(&mut self, value: i64) {
+        let _ = self.output.send(value);
+    }
+}
+
+pub struct FnDevice<I, O> {
+    input: I,
+    output: O,
+}
+
+pub fn from_fn<I, O>(input: I, output: O) -> FnDevice<I, O>
+where
+    I: FnMut() -> Option<i64>,
+    O: FnMut(i64),
+{
+    FnDevice { input, output }
+}
+
+impl<I, O> Device for FnDevice<I, O>
+where
+    I: FnMut() -> Option<i64>,
+    O: FnMut(i64),
+{
+    fn input(&mut self) -> Option<i64> {
+        (self.input)()
+    }
+
+    fn output(&mut self, value: i64) {
+        (self.output)(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Input(i64),
+    Output(i64),
+}
+
+// Passes everything through to `device` and keeps a log of it
+pub struct Recording<D> {
+    pub device: D,
+    pub events: Vec<Event>,
+}
+
+impl<D: Device> Recording<D> {
+    pub fn new(device: D) -> Recording<D> {
+        Recording {
+            device,
+            events: vec![],
+        }
+    }
+}
+
+impl<D: Device> Device for Recording<D> {
+    fn input(&mut self) -> Option<i64> {
+        let v = self.device.input()?;
+        self.events.push(Event::Input(v));
+        Some(v)
+    }
+
+    fn output(&mut self, value: i64) {
+        self.events.push(Event::Output(value));
+        self.device.output(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IntcodeError;
+
+    // doubles every input until it reads a zero
+    const DOUBLE: &str = "3,15,1006,15,14,1002,15,2,15,4,15,1105,1,0,99,0";
+
+    fn machine() -> IntComputer {
+        DOUBLE.parse().unwrap()
+    }
+
+    #[test]
+    fn queue_test() {
+        let mut queue = Queue::new(&[1, 2, 3]);
+        let mut intcomp = machine();
+        assert_eq!(intcomp.run_device(&mut queue), Ok(Status::NeedsInput));
+        assert_eq!(queue.output, [2, 4, 6]);
+        // output goes straight to the device
+        assert!(intcomp.output.is_empty());
+
+        queue.input.push_back(0);
+        assert_eq!(intcomp.run_device(&mut queue), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn limits_test() {
+        // input queued on the machine comes first, and steps count as usual
+        let mut intcomp = machine();
+        intcomp.push_input(1);
+        intcomp.limits.max_steps = Some(6);
+        let mut queue = Queue::new(&[2, 3]);
+        assert!(matches!(
+            intcomp.run_device(&mut queue),
+            Err(IntcodeError::StepLimitExceeded { steps: 6, .. })
+        ));
+        assert_eq!(queue.output, [2]);
+        assert_eq!(queue.input, [3]);
+        assert_eq!(intcomp.steps, 6);
+    }
+
+    #[test]
+    fn closure_test() {
+        let mut next = 0;
+        let mut screen = vec![];
+        let mut device = from_fn(
+            || {
+                next += 5;
+                Some(next % 15)
+            },
+            |v| screen.push(v),
+        );
+        assert_eq!(machine().run_device(&mut device), Ok(Status::Halted));
+        assert_eq!(screen, [10, 20]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn text_test() {
+        let mut out = vec![];
+        let mut text = Text::new("7\n\n-1\n0\n".as_bytes(), &mut out);
+        let mut recording = Recording::new(&mut text);
+        assert_eq!(machine().run_device(&mut recording), Ok(Status::Halted));
+        assert_eq!(
+            recording.events,
+            [
+                Event::Input(7),
+                Event::Output(14),
+                Event::Input(-1),
+                Event::Output(-2),
+                Event::Input(0)
+            ]
+        );
+        assert!(text.error.is_none());
+        drop(text);
+        assert_eq!(String::from_utf8(out).unwrap(), "14\n-2\n");
+
+        let mut text = Text::new("3\nx\n4\n".as_bytes(), vec![]);
+        assert_eq!(machine().run_device(&mut text), Ok(Status::NeedsInput));
+        assert_eq!(text.error.unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn channel_test() {
+        let (mut here, mut there) = Channel::pair();
+        let handle = std::thread::spawn(move || machine().run_device(&mut there));
+        for v in 1..4 {
+            here.output(v);
+            assert_eq!(here.input(), Some(v * 2));
+        }
+        here.output(0);
+        assert_eq!(handle.join().unwrap(), Ok(Status::Halted));
+        assert_eq!(here.input(), None);
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::{Instruction, Memory, Mode};
+
+// Operand syntax: `5` is immediate, `[5]` is position and `[rb+5]` is
+// relative to the relative base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Operand {
+    pub mode: Mode,
+    pub value: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    Instr(Instruction, Vec<Operand>),
+    Data(i64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub addr: usize,
+    pub item: Item,
+}
+
+impl Line {
+    pub fn size(&self) -> usize {
+        match self.item {
+            Item::Instr(ref instr, _) => instr.size(),
+            Item::Data(_) => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listing {
+    pub program: Vec<i64>,
+    pub lines: Vec<Line>,
+    pub labels: BTreeMap<usize, String>,
+}
+
+pub(crate) fn decode_at(program: &[i64], addr: usize) -> Option<Line> {
+    let instr = Instruction::decode(addr, program[addr]).ok()?;
+    if addr + instr.size() > program.len() {
+        return None;
+    }
+
+    let operands = (0..instr.opcode.num_params())
+        .map(|n| Operand {
+            mode: instr.modes[n],
+            value: program[addr + 1 + n],
+        })
+        .collect();
+    Some(Line {
+        addr,
+        item: Item::Instr(instr, operands),
+    })
+}
+
+// Linear sweep over the program image. Cells that do not decode to a
+// complete instruction become `.data`.
+pub fn disassemble(program: &[i64]) -> Listing {
+    let mut lines = vec![];
+    let mut addr = 0;
+    while addr < program.len() {
+        let line = decode_at(program, addr).unwrap_or(Line {
+            addr,
+            item: Item::Data(program[addr]),
+        });
+        addr += line.size();
+        lines.push(line);
+    }
+
+    // Only targets that start a line can carry a label
+    let starts: Vec<usize> = lines.iter().map(|l| l.addr).collect();
+    let mut labels = BTreeMap::new();
+    for line in &lines {
+        let target = match line.item {
+            Item::Instr(instr, ref operands) if instr.opcode.is_jump() => operands[1],
+            _ => continue,
+        };
+        if target.mode != Mode::Immediate {
+            continue;
+        }
+        if let Ok(addr) = usize::try_from(target.value) {
+            if starts.binary_search(&addr).is_ok() {
+                labels.insert(addr, format!("L{}", addr));
+            }
+        }
+    }
+
+    Listing {
+        program: program.to_vec(),
+        lines,
+        labels,
+    }
+}
+
+fn fmt_operand(op: &Operand, jump_target: bool, labels: &BTreeMap<usize, String>) -> String {
+    match op.mode {
+        Mode::Immediate => {
+            let label = match usize::try_from(op.value) {
+                Ok(addr) if jump_target => labels.get(&addr),
+                _ => None,
+            };
+            match label {
+                Some(name) => name.clone(),
+                None => op.value.to_string(),
+            }
+        }
+        Mode::Position => format!("[{}]", op.value),
+        Mode::Relative if op.value < 0 => format!("[rb{}]", op.value),
+        Mode::Relative => format!("[rb+{}]", op.value),
+    }
+}
+
+pub(crate) fn item_text(item: &Item, labels: &BTreeMap<usize, String>) -> String {
+    match *item {
+        Item::Instr(instr, ref operands) => {
+            let ops: Vec<String> = operands
+                .iter()
+                .enumerate()
+                .map(|(n, op)| fmt_operand(op, instr.opcode.is_jump() && n == 1, labels))
+                .collect();
+            format!("{:<5} {}", instr.opcode.mnemonic(), ops.join(", "))
+                .trim_end()
+                .to_string()
+        }
+        Item::Data(value) => format!(".data {}", value),
+    }
+}
+
+// Decode the single instruction at `addr` of a live machine's memory
+pub fn disassemble_one(memory: &Memory, addr: usize) -> (String, usize) {
+    let window: Vec<i64> = (addr..addr + 4).map(|a| memory[a]).collect();
+    let line = decode_at(&window, 0).unwrap_or(Line {
+        addr: 0,
+        item: Item::Data(window[0]),
+    });
+    (item_text(&line.item, &BTreeMap::new()), line.size())
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            if let Some(name) = self.labels.get(&line.addr) {
+                writeln!(f, "{}:", name)?;
+            }
+
+            let text = item_text(&line.item, &self.labels);
+            let raw: Vec<String> = self.program[line.addr..line.addr + line.size()]
+                .iter()
+                .map(|v| v.to_string())
+                .collect();
+            writeln!(f, "{:>6}    {:<32} ; {}", line.addr, text, raw.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    #[test]
+    fn disasm_test() {
+        let program = parse_program("1002,4,3,4,33,99").unwrap();
+        let listing = disassemble(&program);
+        assert_eq!(listing.lines.len(), 3);
+        assert_eq!(listing.lines[1].item, Item::Data(33));
+
+        let text = listing.to_string();
+        let lines: Vec<&str> = text.lines().map(|l| l.trim()).collect();
+        assert!(lines[0].starts_with("0    mul   [4], 3, [4]"));
+        assert!(lines[1].starts_with("4    .data 33"));
+        assert!(lines[2].starts_with("5    hlt"));
+        assert!(lines[2].ends_with("; 99"));
+    }
+
+    #[test]
+    fn label_test() {
+        let program = parse_program("3,3,1105,-1,9,1101,0,0,12,4,12,99,1").unwrap();
+        let listing = disassemble(&program);
+        assert_eq!(listing.labels.get(&9).map(|s| s.as_str()), Some("L9"));
+
+        let text = listing.to_string();
+        assert!(text.contains("jnz   -1, L9"));
+        assert!(text.contains("L9:\n"));
+        assert!(text.contains("out   [12]"));
+    }
+}
@@ -0,0 +1,140 @@
+use core::error::Error;
+use core::fmt;
+
+pub type Result<T> = ::core::result::Result<T, IntcodeError>;
+
+// Every variant records the cursor (`addr`) and the raw instruction (`op`)
+// that was executing when the machine faulted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntcodeError {
+    UnknownOpcode {
+        addr: usize,
+        op: i64,
+    },
+    InvalidMode {
+        addr: usize,
+        op: i64,
+        param: usize,
+        mode: i64,
+    },
+    AddressOutOfRange {
+        addr: usize,
+        op: i64,
+        target: usize,
+        limit: usize,
+    },
+    NegativeAddress {
+        addr: usize,
+        op: i64,
+        target: i64,
+    },
+    InputExhausted {
+        addr: usize,
+        op: i64,
+    },
+    StepLimitExceeded {
+        addr: usize,
+        op: i64,
+        steps: u64,
+    },
+    Overflow {
+        addr: usize,
+        op: i64,
+    },
+    // The machine came back to the same state after `period` instructions,
+    // all of them between `start` and `end`
+    LoopDetected {
+        addr: usize,
+        op: i64,
+        period: u64,
+        start: usize,
+        end: usize,
+    },
+}
+
+impl IntcodeError {
+    pub fn addr(&self) -> usize {
+        match *self {
+            IntcodeError::UnknownOpcode { addr, .. }
+            | IntcodeError::InvalidMode { addr, .. }
+            | IntcodeError::AddressOutOfRange { addr, .. }
+            | IntcodeError::NegativeAddress { addr, .. }
+            | IntcodeError::InputExhausted { addr, .. }
+            | IntcodeError::StepLimitExceeded { addr, .. }
+            | IntcodeError::Overflow { addr, .. }
+            | IntcodeError::LoopDetected { addr, .. } => addr,
+        }
+    }
+
+    pub fn op(&self) -> i64 {
+        match *self {
+            IntcodeError::UnknownOpcode { op, .. }
+            | IntcodeError::InvalidMode { op, .. }
+            | IntcodeError::AddressOutOfRange { op, .. }
+            | IntcodeError::NegativeAddress { op, .. }
+            | IntcodeError::InputExhausted { op, .. }
+            | IntcodeError::StepLimitExceeded { op, .. }
+            | IntcodeError::Overflow { op, .. }
+            | IntcodeError::LoopDetected { op, .. } => op,
+        }
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IntcodeError::UnknownOpcode { addr, op } => {
+                write!(f, "unknown op code {} at address {}", op, addr)
+            }
+            IntcodeError::InvalidMode {
+                addr,
+                op,
+                param,
+                mode,
+            } => write!(
+                f,
+                "invalid mode {} for parameter {} of {} at address {}",
+                mode, param, op, addr
+            ),
+            IntcodeError::AddressOutOfRange {
+                addr,
+                op,
+                target,
+                limit,
+            } => write!(
+                f,
+                "address {} exceeds memory limit of {} cells ({} at address {})",
+                target, limit, op, addr
+            ),
+            IntcodeError::NegativeAddress { addr, op, target } => write!(
+                f,
+                "negative address {} ({} at address {})",
+                target, op, addr
+            ),
+            IntcodeError::InputExhausted { addr, op } => {
+                write!(f, "input exhausted ({} at address {})", op, addr)
+            }
+            IntcodeError::StepLimitExceeded { addr, op, steps } => write!(
+                f,
+                "step limit of {} exceeded ({} at address {})",
+                steps, op, addr
+            ),
+            IntcodeError::Overflow { addr, op } => {
+                write!(f, "arithmetic overflow ({} at address {})", op, addr)
+            }
+            IntcodeError::LoopDetected {
+                addr,
+                op,
+                period,
+                start,
+                end,
+            } => write!(
+                f,
+                "infinite loop between {} and {}, repeating every {} steps ({} at address {})",
+                start, end, period, op, addr
+            ),
+        }
+    }
+}
+
+impl Error for IntcodeError {}
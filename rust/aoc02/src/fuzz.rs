@@ -0,0 +1,425 @@
+// Differential testing: random well-formed programs run on `IntComputer` and
+// on a small reference interpreter in lockstep, comparing the outcome of
+// every step. The seed comes from FUZZ_SEED when set, so a failure can be
+// reproduced, and FUZZ_CASES sets how many programs are tried.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+
+use crate::{IntComputer, IntcodeError, Result, Status};
+
+// xorshift64*
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        // the state must not be zero
+        Rng(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn range(&mut self, lo: i64, hi: i64) -> i64 {
+        lo + self.below((hi - lo) as u64) as i64
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+}
+
+// Mostly valid instructions over a small address space, with the odd bad
+// opcode, immediate write or huge value thrown in to reach the error paths
+fn generate(rng: &mut Rng) -> Vec<i64> {
+    const OPS: [(i64, usize); 10] = [
+        (1, 3),
+        (2, 3),
+        (3, 1),
+        (4, 1),
+        (5, 2),
+        (6, 2),
+        (7, 3),
+        (8, 3),
+        (9, 1),
+        (99, 0),
+    ];
+    let len = rng.range(8, 40);
+    let mut program = vec![];
+    while (program.len() as i64) < len {
+        if rng.chance(2) {
+            program.push(rng.range(-5, 30000));
+            continue;
+        }
+        let (code, params) = OPS[rng.below(OPS.len() as u64) as usize];
+        let mut op = code;
+        let mut operands = vec![];
+        let mut scale = 100;
+        for n in 0..params {
+            let writes = n == params - 1 && [1, 2, 3, 7, 8].contains(&code);
+            let mode = match rng.below(10) {
+                _ if writes && rng.chance(95) => 2 * (rng.below(2) as i64),
+                0..=3 => 0,
+                4..=7 => 1,
+                _ => 2,
+            };
+            op += mode * scale;
+            scale *= 10;
+            operands.push(match mode {
+                _ if rng.chance(3) => [i64::MAX, i64::MIN, 1 << 40][rng.below(3) as usize],
+                0 => rng.range(0, len + 4),
+                2 => rng.range(-3, len),
+                _ => rng.range(-10, len),
+            });
+        }
+        program.push(op);
+        program.extend(operands);
+    }
+    program.push(99);
+    program
+}
+
+// Deliberately plain interpreter to check `IntComputer` against
+struct Reference {
+    memory: BTreeMap<usize, i64>,
+    cursor: usize,
+    base: i64,
+    input: Vec<i64>,
+    output: Vec<i64>,
+}
+
+impl Reference {
+    fn new(program: &[i64], input: &[i64]) -> Reference {
+        Reference {
+            memory: program.iter().copied().enumerate().collect(),
+            cursor: 0,
+            base: 0,
+            input: input.iter().rev().copied().collect(),
+            output: vec![],
+        }
+    }
+
+    fn get(&self, addr: usize) -> i64 {
+        self.memory.get(&addr).copied().unwrap_or(0)
+    }
+
+    // Address of parameter n (0 based) of the current instruction
+    fn addr(&self, n: usize) -> Result<usize> {
+        let op = self.get(self.cursor);
+        let raw = self.get(self.cursor + 1 + n);
+        let target = match op / 10i64.pow(n as u32 + 2) % 10 {
+            0 => raw,
+            1 => return Ok(self.cursor + 1 + n),
+            _ => self.base.checked_add(raw).ok_or(IntcodeError::Overflow {
+                addr: self.cursor,
+                op,
+            })?,
+        };
+        if target < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                addr: self.cursor,
+                op,
+                target,
+            });
+        }
+        Ok(target as usize)
+    }
+
+    fn read(&self, n: usize) -> Result<i64> {
+        Ok(self.get(self.addr(n)?))
+    }
+
+    fn write(&mut self, n: usize, value: i64) -> Result<()> {
+        let op = self.get(self.cursor);
+        if op / 10i64.pow(n as u32 + 2) % 10 == 1 {
+            return Err(IntcodeError::InvalidMode {
+                addr: self.cursor,
+                op,
+                param: n,
+                mode: 1,
+            });
+        }
+        let addr = self.addr(n)?;
+        self.memory.insert(addr, value);
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<Option<Status>> {
+        let (addr, op) = (self.cursor, self.get(self.cursor));
+        let unknown = IntcodeError::UnknownOpcode { addr, op };
+        if op < 0 || !(1..10).contains(&(op % 100)) && op % 100 != 99 {
+            return Err(unknown);
+        }
+        for param in 0..3 {
+            let mode = op / 10i64.pow(param as u32 + 2) % 10;
+            if mode > 2 {
+                return Err(IntcodeError::InvalidMode {
+                    addr,
+                    op,
+                    param,
+                    mode,
+                });
+            }
+        }
+        if op >= 100_000 {
+            return Err(unknown);
+        }
+        let overflow = IntcodeError::Overflow { addr, op };
+
+        match op % 100 {
+            1 => {
+                let v = self.read(0)?.checked_add(self.read(1)?).ok_or(overflow)?;
+                self.write(2, v)?;
+                self.cursor += 4;
+            }
+            2 => {
+                let v = self.read(0)?.checked_mul(self.read(1)?).ok_or(overflow)?;
+                self.write(2, v)?;
+                self.cursor += 4;
+            }
+            3 => {
+                let v = match self.input.pop() {
+                    Some(v) => v,
+                    None => return Ok(Some(Status::NeedsInput)),
+                };
+                self.write(0, v)?;
+                self.cursor += 2;
+            }
+            4 => {
+                let v = self.read(0)?;
+                self.output.push(v);
+                self.cursor += 2;
+                return Ok(Some(Status::ProducedOutput));
+            }
+            5 | 6 => {
+                if (self.read(0)? != 0) == (op % 100 == 5) {
+                    let target = self.read(1)?;
+                    if target < 0 {
+                        return Err(IntcodeError::NegativeAddress { addr, op, target });
+                    }
+                    self.cursor = target as usize;
+                } else {
+                    self.cursor += 3;
+                }
+            }
+            7 => {
+                let v = self.read(0)? < self.read(1)?;
+                self.write(2, v as i64)?;
+                self.cursor += 4;
+            }
+            8 => {
+                let v = self.read(0)? == self.read(1)?;
+                self.write(2, v as i64)?;
+                self.cursor += 4;
+            }
+            9 => {
+                self.base = self.base.checked_add(self.read(0)?).ok_or(overflow)?;
+                self.cursor += 2;
+            }
+            _ => return Ok(Some(Status::Halted)),
+        }
+        Ok(None)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Divergence {
+    Result {
+        expected: Result<Option<Status>>,
+        actual: Result<Option<Status>>,
+    },
+    Registers {
+        expected: (usize, i64),
+        actual: (usize, i64),
+    },
+    Memory {
+        addr: usize,
+        expected: i64,
+        actual: i64,
+    },
+    Output {
+        expected: Vec<i64>,
+        actual: Vec<i64>,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Divergence::Result { expected, actual } => {
+                write!(f, "result {:?}, expected {:?}", actual, expected)
+            }
+            Divergence::Registers { expected, actual } => write!(
+                f,
+                "cursor and relative base {:?}, expected {:?}",
+                actual, expected
+            ),
+            Divergence::Memory {
+                addr,
+                expected,
+                actual,
+            } => write!(f, "[{}] = {}, expected {}", addr, actual, expected),
+            Divergence::Output { expected, actual } => {
+                write!(f, "output {:?}, expected {:?}", actual, expected)
+            }
+        }
+    }
+}
+
+fn check(intcomp: &IntComputer, reference: &Reference) -> Option<Divergence> {
+    let expected = (reference.cursor, reference.base);
+    let actual = (intcomp.cursor, intcomp.relative_base);
+    if expected != actual {
+        return Some(Divergence::Registers { expected, actual });
+    }
+    let output: Vec<i64> = intcomp.output.iter().copied().collect();
+    if output != reference.output {
+        return Some(Divergence::Output {
+            expected: reference.output.clone(),
+            actual: output,
+        });
+    }
+    let addrs = intcomp
+        .memory
+        .iter()
+        .map(|(addr, _)| addr)
+        .chain(reference.memory.keys().copied());
+    for addr in addrs {
+        let (expected, actual) = (reference.get(addr), intcomp.memory[addr]);
+        if expected != actual {
+            return Some(Divergence::Memory {
+                addr,
+                expected,
+                actual,
+            });
+        }
+    }
+    None
+}
+
+// Run both machines one instruction at a time until they stop, returning
+// the first step at which they disagree
+fn compare(
+    intcomp: &mut IntComputer,
+    reference: &mut Reference,
+    max_steps: u64,
+) -> Option<(u64, Divergence)> {
+    for step in 0..max_steps {
+        let expected = reference.step();
+        let actual = intcomp.execute_instr();
+        if expected != actual {
+            return Some((step, Divergence::Result { expected, actual }));
+        }
+        if let Some(divergence) = check(intcomp, reference) {
+            return Some((step, divergence));
+        }
+        match actual {
+            Ok(None) | Ok(Some(Status::ProducedOutput)) => (),
+            _ => break,
+        }
+    }
+    None
+}
+
+struct Failure {
+    seed: u64,
+    program: Vec<i64>,
+    input: Vec<i64>,
+    step: u64,
+    divergence: Divergence,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let program: Vec<String> = self.program.iter().map(|v| v.to_string()).collect();
+        writeln!(f, "divergence with FUZZ_SEED={}", self.seed)?;
+        writeln!(f, "program: {}", program.join(","))?;
+        writeln!(f, "input: {:?}", self.input)?;
+        write!(f, "step {}: {}", self.step, self.divergence)
+    }
+}
+
+// Each case gets a seed of its own, so FUZZ_SEED=<seed> FUZZ_CASES=1
+// replays just the failing program
+fn fuzz(seed: u64, cases: usize) -> Option<Failure> {
+    let mut seeds = Rng::new(seed);
+    for n in 0..cases {
+        let seed = if n == 0 { seed } else { seeds.next() };
+        let mut rng = Rng::new(seed);
+        let program = generate(&mut rng);
+        let input: Vec<i64> = (0..rng.below(4)).map(|_| rng.range(-50, 50)).collect();
+
+        let mut intcomp = IntComputer::new(program.clone());
+        intcomp.input.extend(&input);
+        let mut reference = Reference::new(&program, &input);
+        if let Some((step, divergence)) = compare(&mut intcomp, &mut reference, 500) {
+            return Some(Failure {
+                seed,
+                program,
+                input,
+                step,
+                divergence,
+            });
+        }
+    }
+    None
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+#[test]
+fn fuzz_test() {
+    let seed = env_or("FUZZ_SEED", 2019);
+    let cases = env_or("FUZZ_CASES", 2000);
+    if let Some(failure) = fuzz(seed, cases) {
+        panic!("{}", failure);
+    }
+}
+
+#[test]
+fn generate_test() {
+    // the same seed gives the same program
+    let a = generate(&mut Rng::new(7));
+    assert_eq!(a, generate(&mut Rng::new(7)));
+    assert_ne!(a, generate(&mut Rng::new(8)));
+    assert_eq!(a.last(), Some(&99));
+}
+
+#[test]
+fn divergence_test() {
+    // a machine that wraps around where the reference faults
+    let program = [1101, i64::MAX, 1, 5, 99, 0];
+    let mut intcomp = IntComputer::new(program.to_vec());
+    intcomp.arithmetic = crate::Arithmetic::Wrapping;
+    let mut reference = Reference::new(&program, &[]);
+    let (step, divergence) = compare(&mut intcomp, &mut reference, 10).unwrap();
+    assert_eq!(step, 0);
+    assert_eq!(
+        divergence.to_string(),
+        "result Ok(None), expected Err(Overflow { addr: 0, op: 1101 })"
+    );
+
+    let program = [3, 5, 4, 5, 99, 0];
+    let mut intcomp = IntComputer::new(program.to_vec());
+    intcomp.push_input(1);
+    let mut reference = Reference::new(&program, &[2]);
+    let (step, divergence) = compare(&mut intcomp, &mut reference, 10).unwrap();
+    assert_eq!(
+        (step, divergence.to_string()),
+        (0, "[5] = 1, expected 2".into())
+    );
+}
@@ -0,0 +1,357 @@
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::trace::{TraceEntry, Tracer};
+use crate::{IntComputer, Opcode, Result, Status};
+
+// What it takes to undo one instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Undo {
+    cursor: usize,
+    relative_base: i64,
+    write: Option<(usize, i64)>,
+    // `IntComputer::steps` before the step
+    steps: u64,
+}
+
+// The nondeterministic part of a step, kept so a segment can be replayed
+// from its checkpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Io {
+    None,
+    Input(i64),
+    Output,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Logged {
+    io: Io,
+    write: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Checkpoint {
+    step: u64,
+    state: IntComputer,
+}
+
+// Record of recent execution for stepping backwards. A full copy of the
+// machine is kept every `interval` steps, and an undo entry for every step
+// since the latest copy. Going back past that copy restores the one before
+// and replays forward with the input the program consumed the first time.
+// Only the last `max_checkpoints` copies are kept, which bounds memory use
+// and how far back the machine can go.
+//
+// Rewinding takes consumed input back to the front of the input queue and
+// removes produced output from the back of the output queue.
+#[derive(Debug, Clone)]
+pub struct History {
+    interval: u64,
+    max_checkpoints: usize,
+    checkpoints: VecDeque<Checkpoint>,
+    undo: Vec<Undo>,
+    // one entry per step since the oldest checkpoint
+    log: VecDeque<Logged>,
+    step: u64,
+    // the machine's step count when the step being traced started
+    machine_steps: u64,
+}
+
+impl Tracer for History {
+    fn trace(&mut self, entry: &TraceEntry) {
+        let relative_base = match entry.opcode {
+            Opcode::AdjustBase => entry.relative_base.wrapping_sub(entry.operands[0]),
+            _ => entry.relative_base,
+        };
+        self.undo.push(Undo {
+            cursor: entry.cursor,
+            relative_base,
+            write: entry.write.map(|w| (w.addr, w.old)),
+            steps: self.machine_steps,
+        });
+        let io = match (entry.input, entry.output) {
+            (Some(v), _) => Io::Input(v),
+            (_, Some(_)) => Io::Output,
+            _ => Io::None,
+        };
+        self.log.push_back(Logged {
+            io,
+            write: entry.write.map(|w| w.addr),
+        });
+        self.step += 1;
+    }
+}
+
+impl History {
+    // Step numbers continue from `step`
+    pub fn new(step: u64, interval: u64, max_checkpoints: usize) -> History {
+        History {
+            interval: interval.max(1),
+            max_checkpoints: max_checkpoints.max(1),
+            checkpoints: VecDeque::new(),
+            undo: vec![],
+            log: VecDeque::new(),
+            step,
+            machine_steps: 0,
+        }
+    }
+
+    pub fn step(&self) -> u64 {
+        self.step
+    }
+
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    // The earliest step the machine can be rewound to
+    pub fn oldest(&self) -> u64 {
+        self.checkpoints.front().map_or(self.step, |c| c.step)
+    }
+
+    fn log_index(&self, step: u64) -> usize {
+        (step - self.oldest()) as usize
+    }
+
+    fn checkpoint(&mut self, intcomp: &IntComputer) {
+        if self.checkpoints.back().map(|c| c.step) == Some(self.step) {
+            return;
+        }
+        if !self.checkpoints.is_empty() && !self.step.is_multiple_of(self.interval) {
+            return;
+        }
+        self.checkpoints.push_back(Checkpoint {
+            step: self.step,
+            state: intcomp.clone(),
+        });
+        self.undo.clear();
+        if self.checkpoints.len() > self.max_checkpoints {
+            let old = self.checkpoints.pop_front().unwrap();
+            let drop = (self.oldest() - old.step) as usize;
+            self.log.drain(..drop);
+        }
+    }
+
+    fn pop(&mut self, intcomp: &mut IntComputer) -> bool {
+        let undo = match self.undo.pop() {
+            Some(undo) => undo,
+            None => return false,
+        };
+        intcomp.cursor = undo.cursor;
+        intcomp.relative_base = undo.relative_base;
+        intcomp.steps = undo.steps;
+        if let Some((addr, old)) = undo.write {
+            // the cell was written before, so this cannot exceed the limit
+            intcomp.memory.set(addr, old).unwrap();
+        }
+        match self.log.pop_back().map(|l| l.io) {
+            Some(Io::Input(v)) => intcomp.input.push_front(v),
+            Some(Io::Output) => {
+                intcomp.output.pop_back();
+            }
+            _ => (),
+        }
+        self.step -= 1;
+        true
+    }
+}
+
+impl IntComputer {
+    // Same as `execute_instr`, recording the step in `history`. Halting is
+    // not recorded since it changes nothing. Like `execute_instr` it leaves
+    // `steps` alone, but going back restores it.
+    pub fn execute_recorded(&mut self, history: &mut History) -> Result<Option<Status>> {
+        history.checkpoint(self);
+        if self.get_op() == Opcode::Halt.code() {
+            return self.execute_instr();
+        }
+        history.machine_steps = self.steps;
+        self.execute_traced(history)
+    }
+
+    // Undo the last recorded step. Returns false at the start of history.
+    pub fn step_back(&mut self, history: &mut History) -> Result<bool> {
+        if history.step == history.oldest() {
+            return Ok(false);
+        }
+        if history.pop(self) {
+            return Ok(true);
+        }
+        let target = history.step - 1;
+        self.rewind_to(history, target)
+    }
+
+    // Go back to the state just before step `target` ran. Returns false if
+    // it is not within the recorded history.
+    pub fn rewind_to(&mut self, history: &mut History, target: u64) -> Result<bool> {
+        if target < history.oldest() || target > history.step {
+            return Ok(false);
+        }
+        let last = history.checkpoints.back().map_or(history.step, |c| c.step);
+        if target >= last {
+            while history.step > target {
+                history.pop(self);
+            }
+            return Ok(true);
+        }
+
+        // restore the latest checkpoint at or before the target and replay
+        while history.checkpoints.back().unwrap().step > target {
+            history.checkpoints.pop_back();
+        }
+        let from = history.checkpoints.back().unwrap().step;
+        let (start, mid) = (history.log_index(from), history.log_index(target));
+        let mut replay_input = vec![];
+        let mut input = VecDeque::new();
+        let mut output = self.output.clone();
+        for (i, logged) in history.log.iter().enumerate().skip(start) {
+            match logged.io {
+                Io::Input(v) if i < mid => replay_input.push(v),
+                Io::Input(v) => input.push_back(v),
+                Io::Output if i >= mid => {
+                    output.pop_back();
+                }
+                _ => (),
+            }
+        }
+        input.extend(&self.input);
+
+        let mut state = history.checkpoints.back().unwrap().state.clone();
+        state.input = replay_input.into_iter().collect();
+        // replayed steps count as they did the first time
+        let steps = state.steps + (target - from);
+        history.log.truncate(start);
+        history.undo.clear();
+        history.step = from;
+        while history.step < target {
+            state.execute_recorded(history)?;
+        }
+        state.input = input;
+        state.output = output;
+        state.steps = steps;
+        *self = state;
+        Ok(true)
+    }
+
+    // Go back to just before the last recorded write to `addr`. Returns the
+    // step that made it, or None (leaving the machine at the start of
+    // history) if no recorded step wrote there.
+    pub fn run_back_to_write(&mut self, history: &mut History, addr: usize) -> Result<Option<u64>> {
+        let oldest = history.oldest();
+        let found = history.log.iter().rposition(|l| l.write == Some(addr));
+        let target = found.map_or(oldest, |i| oldest + i as u64);
+        self.rewind_to(history, target)?;
+        Ok(found.map(|_| target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // sums its inputs until it reads a zero, printing the running total:
+    //
+    //     loop:   in    [x]
+    //             jz    [x], done
+    //             add   [total], [x], [total]
+    //             out   [total]
+    //             jnz   1, loop
+    //     done:   hlt
+    //     x:      .data 0
+    //     total:  .data 0
+    const SUM: &str = "3,15,1006,15,14,1,16,15,16,4,16,1105,1,0,99,0,0";
+    const TOTAL: usize = 16;
+
+    fn record(history: &mut History, intcomp: &mut IntComputer) -> Vec<IntComputer> {
+        let mut states = vec![intcomp.clone()];
+        while let Ok(None) | Ok(Some(Status::ProducedOutput)) = intcomp.execute_recorded(history) {
+            states.push(intcomp.clone());
+        }
+        states
+    }
+
+    fn same(a: &IntComputer, b: &IntComputer) -> bool {
+        a.cursor == b.cursor
+            && a.relative_base == b.relative_base
+            && a.input == b.input
+            && a.output == b.output
+            && (0..40).all(|i| a.memory[i] == b.memory[i])
+    }
+
+    #[test]
+    fn step_back_test() {
+        let mut intcomp: IntComputer = SUM.parse().unwrap();
+        intcomp.input.extend(&[3, 4, 5, 0]);
+        let mut history = History::new(0, 4, 100);
+        let states = record(&mut history, &mut intcomp);
+        assert_eq!(history.step(), 17);
+        assert_eq!(intcomp.output, [3, 7, 12]);
+
+        for n in (0..states.len() - 1).rev() {
+            assert!(intcomp.step_back(&mut history).unwrap());
+            assert_eq!(history.step(), n as u64);
+            assert!(same(&intcomp, &states[n]), "step {}", n);
+        }
+        assert!(!intcomp.step_back(&mut history).unwrap());
+    }
+
+    #[test]
+    fn rewind_test() {
+        let mut intcomp: IntComputer = SUM.parse().unwrap();
+        intcomp.input.extend(&[3, 4, 5, 0]);
+        let mut history = History::new(0, 4, 100);
+        let states = record(&mut history, &mut intcomp);
+
+        assert!(intcomp.rewind_to(&mut history, 7).unwrap());
+        assert!(same(&intcomp, &states[7]));
+        assert!(intcomp.rewind_to(&mut history, 6).unwrap());
+        assert!(same(&intcomp, &states[6]));
+        assert!(!intcomp.rewind_to(&mut history, 9).unwrap());
+
+        // running forward again takes the same path
+        let again = record(&mut history, &mut intcomp);
+        assert!(same(&intcomp, &states[17]));
+        assert_eq!(again.len(), 17 - 6 + 1);
+    }
+
+    #[test]
+    fn bounded_test() {
+        let mut intcomp: IntComputer = SUM.parse().unwrap();
+        intcomp.input.extend(&[3, 4, 5, 0]);
+        let mut history = History::new(0, 4, 2);
+        let states = record(&mut history, &mut intcomp);
+
+        // checkpoints at 12 and 16 are the only ones left
+        assert_eq!(history.oldest(), 12);
+        assert!(!intcomp.rewind_to(&mut history, 11).unwrap());
+        assert!(intcomp.rewind_to(&mut history, 13).unwrap());
+        assert!(same(&intcomp, &states[13]));
+        assert!(intcomp.step_back(&mut history).unwrap());
+        assert!(!intcomp.step_back(&mut history).unwrap());
+        assert!(same(&intcomp, &states[12]));
+    }
+
+    #[test]
+    fn last_write_test() {
+        let mut intcomp: IntComputer = SUM.parse().unwrap();
+        intcomp.input.extend(&[3, 4, 5, 0]);
+        let mut history = History::new(0, 4, 100);
+        let states = record(&mut history, &mut intcomp);
+
+        // the last add ran as step 12 and wrote 12 into the total
+        assert_eq!(
+            intcomp.run_back_to_write(&mut history, TOTAL).unwrap(),
+            Some(12)
+        );
+        assert!(same(&intcomp, &states[12]));
+        assert_eq!(intcomp.memory[TOTAL], 7);
+        assert_eq!(
+            intcomp.run_back_to_write(&mut history, TOTAL).unwrap(),
+            Some(7)
+        );
+        assert_eq!(intcomp.memory[TOTAL], 3);
+        assert_eq!(intcomp.run_back_to_write(&mut history, 1000).unwrap(), None);
+        assert_eq!(history.step(), 0);
+    }
+}
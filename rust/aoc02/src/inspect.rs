@@ -0,0 +1,265 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::trace::{TraceEntry, Tracer};
+use crate::{IntComputer, IntcodeError, Result, Status};
+
+// Step of the last write to each cell
+#[derive(Debug, Clone, Default)]
+struct LastWrites {
+    step: u64,
+    cells: BTreeMap<usize, u64>,
+}
+
+impl Tracer for LastWrites {
+    fn trace(&mut self, entry: &TraceEntry) {
+        if let Some(write) = entry.write {
+            self.cells.insert(write.addr, self.step);
+        }
+        self.step += 1;
+    }
+}
+
+// Final state of one patched run. A run that faults keeps the state it
+// faulted in, with the error in `result`.
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub intcomp: IntComputer,
+    pub result: Result<()>,
+    writes: LastWrites,
+}
+
+fn run(intcomp: &mut IntComputer, patch: &[(usize, i64)], writes: &mut LastWrites) -> Result<()> {
+    for &(addr, value) in patch {
+        intcomp.store(addr, value)?;
+    }
+    loop {
+        match intcomp.run_traced(writes)? {
+            Status::ProducedOutput => continue,
+            Status::Halted => return Ok(()),
+            Status::NeedsInput => {
+                return Err(IntcodeError::InputExhausted {
+                    addr: intcomp.cursor,
+                    op: intcomp.get_op(),
+                })
+            }
+        }
+    }
+}
+
+pub fn run_patched(program: &IntComputer, patch: &[(usize, i64)]) -> Run {
+    let mut intcomp = program.clone();
+    let mut writes = LastWrites::default();
+    let result = run(&mut intcomp, patch, &mut writes);
+    Run {
+        intcomp,
+        result,
+        writes,
+    }
+}
+
+// A cell that ends up different, `before` in the first run and `after` in
+// the second, with the step of each run's last write to it. The two runs
+// count steps separately, so the steps only say when within its own run a
+// cell was written. A cell neither run wrote differs because of the patches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    pub addr: usize,
+    pub before: i64,
+    pub after: i64,
+    pub first_write: Option<u64>,
+    pub second_write: Option<u64>,
+}
+
+pub fn diff(first: &Run, second: &Run) -> Vec<Change> {
+    let addrs: BTreeSet<usize> = first
+        .intcomp
+        .memory
+        .iter()
+        .chain(second.intcomp.memory.iter())
+        .map(|(addr, _)| addr)
+        .collect();
+    addrs
+        .into_iter()
+        .filter_map(|addr| {
+            let before = first.intcomp.memory[addr];
+            let after = second.intcomp.memory[addr];
+            if before == after {
+                return None;
+            }
+            Some(Change {
+                addr,
+                before,
+                after,
+                first_write: first.writes.cells.get(&addr).copied(),
+                second_write: second.writes.cells.get(&addr).copied(),
+            })
+        })
+        .collect()
+}
+
+fn format_step(step: Option<u64>) -> String {
+    match step {
+        Some(step) => format!("step {}", step),
+        None => "-".to_string(),
+    }
+}
+
+pub fn format_diff(changes: &[Change]) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{:>8}  {:>20}  {:>20}  {:<12}  run 2 write",
+        "addr", "before", "after", "run 1 write"
+    )
+    .unwrap();
+    for c in changes {
+        let first = format_step(c.first_write);
+        writeln!(
+            out,
+            "{:>8}  {:>20}  {:>20}  {:<12}  {}",
+            c.addr,
+            c.before,
+            c.after,
+            first,
+            format_step(c.second_write)
+        )
+        .unwrap();
+    }
+    out
+}
+
+// Memory laid out like hexdump: an address, `width` cells and the cells as
+// text, with `>` in front of the cell under the cursor. A row repeating the
+// one above it is shown as `*`, and the last line is where memory ends.
+pub fn hexdump(intcomp: &IntComputer, width: usize) -> String {
+    let width = width.max(1);
+    let memory = &intcomp.memory;
+    let end = memory.iter().map(|(addr, _)| addr + 1).max().unwrap_or(0);
+    let cell_width = memory
+        .iter()
+        .map(|(_, v)| v.to_string().len())
+        .max()
+        .unwrap_or(1);
+    let rows: BTreeSet<usize> = memory.iter().map(|(addr, _)| addr / width).collect();
+
+    let mut out = String::new();
+    let mut prev: Option<Vec<i64>> = None;
+    let mut starred = false;
+    let mut line = |row: usize, out: &mut String| {
+        let start = row * width;
+        let cells: Vec<i64> = (start..start + width).map(|a| memory[a]).collect();
+        if prev.as_ref() == Some(&cells) {
+            if !starred {
+                writeln!(out, "*").unwrap();
+                starred = true;
+            }
+            return;
+        }
+        write!(out, "{:>8} ", start).unwrap();
+        let mut text = String::new();
+        for (addr, &v) in (start..).zip(&cells) {
+            let marker = if addr == intcomp.cursor { '>' } else { ' ' };
+            write!(out, " {}{:>w$}", marker, v, w = cell_width).unwrap();
+            text.push(match v {
+                32..=126 => v as u8 as char,
+                _ => '.',
+            });
+        }
+        writeln!(out, "  |{}|", text).unwrap();
+        prev = Some(cells);
+        starred = false;
+    };
+
+    let mut next = 0;
+    for row in rows {
+        // rows that were never written are all zero
+        if row > next {
+            line(next, &mut out);
+            if row > next + 1 {
+                line(next + 1, &mut out);
+            }
+        }
+        line(row, &mut out);
+        next = row + 1;
+    }
+    writeln!(out, "{:>8}", end).unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // [11] = [9] + [10], then [12] = [9] + 5
+    const PROG: &str = "1,9,10,11,1001,9,5,12,99,3,4,0,0";
+
+    fn change(addr: usize, before: i64, after: i64, writes: (Option<u64>, Option<u64>)) -> Change {
+        Change {
+            addr,
+            before,
+            after,
+            first_write: writes.0,
+            second_write: writes.1,
+        }
+    }
+
+    #[test]
+    fn diff_test() {
+        let program: IntComputer = PROG.parse().unwrap();
+        let first = run_patched(&program, &[]);
+        assert_eq!(first.result, Ok(()));
+
+        let second = run_patched(&program, &[(9, 10)]);
+        assert_eq!(
+            diff(&first, &second),
+            [
+                change(9, 3, 10, (None, None)),
+                change(11, 7, 14, (Some(0), Some(0))),
+                change(12, 8, 15, (Some(1), Some(1))),
+            ]
+        );
+
+        // the first add writes [12] instead, which the second one overwrites
+        let second = run_patched(&program, &[(3, 12)]);
+        let changes = diff(&first, &second);
+        assert_eq!(
+            changes,
+            [
+                change(3, 11, 12, (None, None)),
+                change(11, 7, 0, (Some(0), None)),
+            ]
+        );
+        assert_eq!(
+            format_diff(&changes),
+            "    addr                before                 after  run 1 write   run 2 write\n\
+             \x20      3                    11                    12  -             -\n\
+             \x20     11                     7                     0  step 0        -\n"
+        );
+
+        let second = run_patched(&program, &[(0, 77)]);
+        assert_eq!(
+            second.result,
+            Err(IntcodeError::UnknownOpcode { addr: 0, op: 77 })
+        );
+        assert_eq!(diff(&first, &second).len(), 3);
+    }
+
+    #[test]
+    fn hexdump_test() {
+        let mut intcomp: IntComputer = PROG.parse().unwrap();
+        intcomp.cursor = 4;
+        intcomp.memory.set(13, 72).unwrap();
+        intcomp.memory.set(14, 105).unwrap();
+        intcomp.memory.set(50, -1).unwrap();
+        let lines = [
+            "       0      1     9    10    11 >1001     9     5    12  |........|",
+            "       8     99     3     4     0     0    72   105     0  |c....Hi.|",
+            "      16      0     0     0     0     0     0     0     0  |........|",
+            "*",
+            "      48      0     0    -1     0     0     0     0     0  |........|",
+            "      51",
+        ];
+        assert_eq!(hexdump(&intcomp, 8), lines.join("\n") + "\n");
+    }
+}
@@ -0,0 +1,707 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::hash::Hash;
+use core::str::FromStr;
+
+use device::{Device, Queues};
+use limits::LoopDetector;
+
+#[cfg(feature = "std")]
+pub mod amplifier;
+#[cfg(feature = "std")]
+pub mod ascii;
+#[cfg(feature = "std")]
+pub mod asm;
+#[cfg(feature = "std")]
+pub mod big;
+#[cfg(feature = "std")]
+pub mod bigint;
+#[cfg(feature = "std")]
+pub mod cached;
+#[cfg(feature = "std")]
+pub mod cfg;
+#[cfg(feature = "std")]
+pub mod debug;
+pub mod device;
+#[cfg(feature = "std")]
+pub mod disasm;
+mod error;
+#[cfg(all(test, feature = "std"))]
+mod fuzz;
+pub mod history;
+#[cfg(feature = "std")]
+pub mod inspect;
+mod limits;
+mod memory;
+#[cfg(feature = "std")]
+pub mod network;
+mod parse;
+#[cfg(feature = "std")]
+pub mod profile;
+#[cfg(feature = "std")]
+pub mod search;
+#[cfg(feature = "std")]
+pub mod snapshot;
+#[cfg(feature = "std")]
+pub mod symbolic;
+pub mod trace;
+
+pub use error::{IntcodeError, Result};
+pub use limits::Limits;
+pub use memory::{LimitExceeded, Memory};
+pub use parse::{parse_program, ParseError, ParseErrorKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl Mode {
+    fn from_digit(digit: i64) -> Option<Mode> {
+        match digit {
+            0 => Some(Mode::Position),
+            1 => Some(Mode::Immediate),
+            2 => Some(Mode::Relative),
+            _ => None,
+        }
+    }
+
+    fn digit(self) -> i64 {
+        match self {
+            Mode::Position => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Add,
+    Mul,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustBase,
+    Halt,
+}
+
+impl Opcode {
+    pub const ALL: [Opcode; 10] = [
+        Opcode::Add,
+        Opcode::Mul,
+        Opcode::Input,
+        Opcode::Output,
+        Opcode::JumpIfTrue,
+        Opcode::JumpIfFalse,
+        Opcode::LessThan,
+        Opcode::Equals,
+        Opcode::AdjustBase,
+        Opcode::Halt,
+    ];
+
+    pub fn from_code(code: i64) -> Option<Opcode> {
+        let op = match code {
+            1 => Opcode::Add,
+            2 => Opcode::Mul,
+            3 => Opcode::Input,
+            4 => Opcode::Output,
+            5 => Opcode::JumpIfTrue,
+            6 => Opcode::JumpIfFalse,
+            7 => Opcode::LessThan,
+            8 => Opcode::Equals,
+            9 => Opcode::AdjustBase,
+            99 => Opcode::Halt,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    pub fn code(self) -> i64 {
+        match self {
+            Opcode::Add => 1,
+            Opcode::Mul => 2,
+            Opcode::Input => 3,
+            Opcode::Output => 4,
+            Opcode::JumpIfTrue => 5,
+            Opcode::JumpIfFalse => 6,
+            Opcode::LessThan => 7,
+            Opcode::Equals => 8,
+            Opcode::AdjustBase => 9,
+            Opcode::Halt => 99,
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Add => "add",
+            Opcode::Mul => "mul",
+            Opcode::Input => "in",
+            Opcode::Output => "out",
+            Opcode::JumpIfTrue => "jnz",
+            Opcode::JumpIfFalse => "jz",
+            Opcode::LessThan => "lt",
+            Opcode::Equals => "eq",
+            Opcode::AdjustBase => "arb",
+            Opcode::Halt => "hlt",
+        }
+    }
+
+    pub fn from_mnemonic(name: &str) -> Option<Opcode> {
+        Opcode::ALL.iter().cloned().find(|op| op.mnemonic() == name)
+    }
+
+    // Index of the parameter the instruction writes its result to, if any
+    pub fn write_param(self) -> Option<usize> {
+        match self {
+            Opcode::Add | Opcode::Mul | Opcode::LessThan | Opcode::Equals => Some(2),
+            Opcode::Input => Some(0),
+            _ => None,
+        }
+    }
+
+    pub fn is_jump(self) -> bool {
+        self == Opcode::JumpIfTrue || self == Opcode::JumpIfFalse
+    }
+
+    pub fn num_params(self) -> usize {
+        match self {
+            Opcode::Add | Opcode::Mul | Opcode::LessThan | Opcode::Equals => 3,
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => 2,
+            Opcode::Input | Opcode::Output | Opcode::AdjustBase => 1,
+            Opcode::Halt => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    pub modes: [Mode; 3],
+}
+
+impl Instruction {
+    // The two lowest digits are the opcode, each higher digit is the mode
+    // of the matching parameter (hundreds -> first parameter, etc.)
+    pub fn decode(addr: usize, raw: i64) -> Result<Instruction> {
+        let unknown = IntcodeError::UnknownOpcode { addr, op: raw };
+        if raw < 0 {
+            return Err(unknown);
+        }
+        let opcode = Opcode::from_code(raw % 100).ok_or(unknown)?;
+
+        let mut modes = [Mode::Position; 3];
+        let mut digits = raw / 100;
+        for (param, mode) in modes.iter_mut().enumerate() {
+            *mode = Mode::from_digit(digits % 10).ok_or(IntcodeError::InvalidMode {
+                addr,
+                op: raw,
+                param,
+                mode: digits % 10,
+            })?;
+            digits /= 10;
+        }
+        if digits != 0 {
+            return Err(unknown);
+        }
+
+        Ok(Instruction { opcode, modes })
+    }
+
+    pub fn encode(&self) -> i64 {
+        let mut raw = self.opcode.code();
+        let mut scale = 100;
+        for mode in &self.modes[..self.opcode.num_params()] {
+            raw += mode.digit() * scale;
+            scale *= 10;
+        }
+        raw
+    }
+
+    pub fn size(&self) -> usize {
+        1 + self.opcode.num_params()
+    }
+}
+
+// Why `run` handed control back to the caller
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Halted,
+    NeedsInput,
+    ProducedOutput,
+}
+
+// How `add`, `mul` and relative base arithmetic treat results that do not
+// fit in an i64. Programs that need the full values run on `BigInt` cells
+// instead, see `big`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Arithmetic {
+    #[default]
+    Checked,
+    Wrapping,
+}
+
+// What a memory cell holds: i64, or `BigInt` for arbitrary precision.
+// Addresses, the relative base and opcodes always have to fit in an i64.
+pub trait Cell:
+    'static + Clone + Default + Ord + Hash + fmt::Debug + fmt::Display + From<i64>
+{
+    // Backs reads of unwritten cells
+    fn zero() -> &'static Self;
+
+    fn to_i64(&self) -> Option<i64>;
+
+    // None when the result does not fit under `arithmetic`
+    fn add(&self, other: &Self, arithmetic: Arithmetic) -> Option<Self>;
+
+    fn mul(&self, other: &Self, arithmetic: Arithmetic) -> Option<Self>;
+
+    // Closest i64, for error reports
+    fn saturate(&self) -> i64 {
+        match self.to_i64() {
+            Some(v) => v,
+            None if *self < Self::default() => i64::MIN,
+            None => i64::MAX,
+        }
+    }
+}
+
+impl Cell for i64 {
+    fn zero() -> &'static i64 {
+        &0
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        Some(*self)
+    }
+
+    fn add(&self, other: &i64, arithmetic: Arithmetic) -> Option<i64> {
+        match arithmetic {
+            Arithmetic::Checked => self.checked_add(*other),
+            Arithmetic::Wrapping => Some(self.wrapping_add(*other)),
+        }
+    }
+
+    fn mul(&self, other: &i64, arithmetic: Arithmetic) -> Option<i64> {
+        match arithmetic {
+            Arithmetic::Checked => self.checked_mul(*other),
+            Arithmetic::Wrapping => Some(self.wrapping_mul(*other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntComputer<C = i64> {
+    pub memory: Memory<C>,
+    pub cursor: usize,
+    pub relative_base: i64,
+    pub input: VecDeque<C>,
+    pub output: VecDeque<C>,
+    pub limits: Limits,
+    pub arithmetic: Arithmetic,
+    // instructions executed by `run` so far
+    pub steps: u64,
+    detector: LoopDetector,
+}
+
+impl<C: Cell> IntComputer<C> {
+    pub fn new(program: Vec<C>) -> IntComputer<C> {
+        IntComputer {
+            memory: Memory::new(program),
+            cursor: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            limits: Limits::default(),
+            arithmetic: Arithmetic::default(),
+            steps: 0,
+            detector: LoopDetector::new(),
+        }
+    }
+
+    pub fn push_input(&mut self, value: C) {
+        self.input.push_back(value);
+    }
+
+    pub fn pop_output(&mut self) -> Option<C> {
+        self.output.pop_front()
+    }
+
+    pub fn get_op(&self) -> i64 {
+        self.memory[self.cursor].saturate()
+    }
+
+    fn check_addr(&self, target: C) -> Result<usize> {
+        let target = self.small(target)?;
+        if target < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                addr: self.cursor,
+                op: self.get_op(),
+                target,
+            });
+        }
+        usize::try_from(target).map_err(|_| self.overflow())
+    }
+
+    fn param_addr(&self, instr: &Instruction, n: usize) -> Result<usize> {
+        let raw = self.memory.get(self.cursor + 1 + n);
+        match instr.modes[n] {
+            Mode::Position => self.check_addr(raw),
+            Mode::Relative => self.check_addr(self.add(C::from(self.relative_base), raw)?),
+            Mode::Immediate => Ok(self.cursor + 1 + n),
+        }
+    }
+
+    fn read_param(&self, instr: &Instruction, n: usize) -> Result<C> {
+        let addr = self.param_addr(instr, n)?;
+        Ok(self.memory.get(addr))
+    }
+
+    fn write_param(&mut self, instr: &Instruction, n: usize, value: C) -> Result<()> {
+        if instr.modes[n] == Mode::Immediate {
+            return Err(IntcodeError::InvalidMode {
+                addr: self.cursor,
+                op: self.get_op(),
+                param: n,
+                mode: 1,
+            });
+        }
+        let addr = self.param_addr(instr, n)?;
+        self.store(addr, value)
+    }
+
+    fn overflow(&self) -> IntcodeError {
+        IntcodeError::Overflow {
+            addr: self.cursor,
+            op: self.get_op(),
+        }
+    }
+
+    fn add(&self, a: C, b: C) -> Result<C> {
+        a.add(&b, self.arithmetic).ok_or_else(|| self.overflow())
+    }
+
+    fn mul(&self, a: C, b: C) -> Result<C> {
+        a.mul(&b, self.arithmetic).ok_or_else(|| self.overflow())
+    }
+
+    // A value that has to fit in an i64
+    fn small(&self, value: C) -> Result<i64> {
+        value.to_i64().ok_or_else(|| self.overflow())
+    }
+
+    fn store(&mut self, addr: usize, value: C) -> Result<()> {
+        self.memory
+            .set(addr, value)
+            .map_err(|e| IntcodeError::AddressOutOfRange {
+                addr: self.cursor,
+                op: self.get_op(),
+                target: e.addr,
+                limit: e.limit,
+            })
+    }
+
+    // Returns Some(status) when the machine has to stop and hand control
+    // back to the caller, None if it can keep going.
+    pub fn execute_instr(&mut self) -> Result<Option<Status>> {
+        let mut input = core::mem::take(&mut self.input);
+        let mut output = core::mem::take(&mut self.output);
+        let result = self.execute_io(&mut Queues::new(&mut input, &mut output));
+        self.input = input;
+        self.output = output;
+        result
+    }
+
+    // Same as `execute_instr`, with `io` doing the reading and writing
+    pub(crate) fn execute_io(&mut self, io: &mut dyn Device<C>) -> Result<Option<Status>> {
+        let instr = Instruction::decode(self.cursor, self.get_op())?;
+        let mut next = self.cursor + instr.size();
+        let mut status = None;
+
+        match instr.opcode {
+            Opcode::Add => {
+                let v = self.add(self.read_param(&instr, 0)?, self.read_param(&instr, 1)?)?;
+                self.write_param(&instr, 2, v)?;
+            }
+            Opcode::Mul => {
+                let v = self.mul(self.read_param(&instr, 0)?, self.read_param(&instr, 1)?)?;
+                self.write_param(&instr, 2, v)?;
+            }
+            Opcode::Input => {
+                // leave the cursor on the instruction so it is retried on resume
+                let v = match io.input() {
+                    Some(v) => v,
+                    None => return Ok(Some(Status::NeedsInput)),
+                };
+                self.write_param(&instr, 0, v)?;
+            }
+            Opcode::Output => {
+                let v = self.read_param(&instr, 0)?;
+                io.output(v);
+                status = Some(Status::ProducedOutput);
+            }
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let cond = self.read_param(&instr, 0)? != C::default();
+                if cond == (instr.opcode == Opcode::JumpIfTrue) {
+                    let target = self.read_param(&instr, 1)?;
+                    next = self.check_addr(target)?;
+                }
+            }
+            Opcode::LessThan => {
+                let v = self.read_param(&instr, 0)? < self.read_param(&instr, 1)?;
+                self.write_param(&instr, 2, C::from(v as i64))?;
+            }
+            Opcode::Equals => {
+                let v = self.read_param(&instr, 0)? == self.read_param(&instr, 1)?;
+                self.write_param(&instr, 2, C::from(v as i64))?;
+            }
+            Opcode::AdjustBase => {
+                let base = self.add(C::from(self.relative_base), self.read_param(&instr, 0)?)?;
+                self.relative_base = self.small(base)?;
+            }
+            Opcode::Halt => return Ok(Some(Status::Halted)),
+        }
+
+        self.cursor = next;
+        Ok(status)
+    }
+
+    pub fn run(&mut self) -> Result<Status> {
+        loop {
+            let op = self.get_op();
+            self.check_steps()?;
+            let status = self.execute_instr()?;
+            if status == Some(Status::NeedsInput) {
+                return Ok(Status::NeedsInput);
+            }
+            self.count_step(op)?;
+            if let Some(status) = status {
+                return Ok(status);
+            }
+        }
+    }
+
+    // Run a program that is expected to finish with the input it was given
+    pub fn run_to_halt(&mut self) -> Result<()> {
+        match self.run_until_blocked()? {
+            Status::NeedsInput => Err(IntcodeError::InputExhausted {
+                addr: self.cursor,
+                op: self.get_op(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    // Keep going through outputs, stopping only on halt or missing input
+    pub fn run_until_blocked(&mut self) -> Result<Status> {
+        loop {
+            match self.run()? {
+                Status::ProducedOutput => continue,
+                status => return Ok(status),
+            }
+        }
+    }
+}
+
+impl FromStr for IntComputer {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> ::core::result::Result<IntComputer, ParseError> {
+        Ok(IntComputer::new(parse_program(input)?))
+    }
+}
+
+impl TryFrom<&str> for IntComputer {
+    type Error = ParseError;
+
+    fn try_from(input: &str) -> ::core::result::Result<IntComputer, ParseError> {
+        input.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_with_input(prog: &str, input: &[i64]) -> IntComputer {
+        let mut intcomp: IntComputer = prog.parse().unwrap();
+        intcomp.input.extend(input);
+        assert_eq!(intcomp.run_until_blocked().unwrap(), Status::Halted);
+        intcomp
+    }
+
+    #[test]
+    fn decode_test() {
+        let instr = Instruction::decode(0, 1002).unwrap();
+        assert_eq!(instr.opcode, Opcode::Mul);
+        assert_eq!(
+            instr.modes,
+            [Mode::Position, Mode::Immediate, Mode::Position]
+        );
+        assert_eq!(instr.size(), 4);
+        assert_eq!(instr.encode(), 1002);
+        assert_eq!(Instruction::decode(0, 21101).unwrap().encode(), 21101);
+
+        assert_eq!(
+            Instruction::decode(5, 42),
+            Err(IntcodeError::UnknownOpcode { addr: 5, op: 42 })
+        );
+        assert_eq!(
+            Instruction::decode(0, 301),
+            Err(IntcodeError::InvalidMode {
+                addr: 0,
+                op: 301,
+                param: 0,
+                mode: 3
+            })
+        );
+    }
+
+    #[test]
+    fn day2_test() {
+        let intcomp = run_with_input("1,9,10,3,2,3,11,0,99,30,40,50", &[]);
+        assert_eq!(intcomp.memory[0], 3500);
+
+        let intcomp = run_with_input("1,1,1,4,99,5,6,0,99", &[]);
+        assert_eq!(intcomp.memory[0], 30);
+    }
+
+    #[test]
+    fn day5_test() {
+        // equal to 8, position and immediate mode
+        let intcomp = run_with_input("3,9,8,9,10,9,4,9,99,-1,8", &[8]);
+        assert_eq!(intcomp.output, [1]);
+        let intcomp = run_with_input("3,3,1107,-1,8,3,4,3,99", &[9]);
+        assert_eq!(intcomp.output, [0]);
+
+        // jumps
+        let intcomp = run_with_input("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9", &[0]);
+        assert_eq!(intcomp.output, [0]);
+        let intcomp = run_with_input("3,3,1105,-1,9,1101,0,0,12,4,12,99,1", &[5]);
+        assert_eq!(intcomp.output, [1]);
+    }
+
+    #[test]
+    fn resume_test() {
+        // echo two inputs back, doubled
+        let mut intcomp: IntComputer = "3,13,1002,13,2,13,4,13,1105,1,0,99,0,0".parse().unwrap();
+        assert_eq!(intcomp.run().unwrap(), Status::NeedsInput);
+        assert_eq!(intcomp.cursor, 0);
+
+        intcomp.push_input(21);
+        assert_eq!(intcomp.run().unwrap(), Status::ProducedOutput);
+        assert_eq!(intcomp.pop_output(), Some(42));
+        assert_eq!(intcomp.pop_output(), None);
+        assert_eq!(intcomp.run().unwrap(), Status::NeedsInput);
+
+        intcomp.push_input(-3);
+        assert_eq!(intcomp.run().unwrap(), Status::ProducedOutput);
+        assert_eq!(intcomp.pop_output(), Some(-6));
+    }
+
+    #[test]
+    fn memory_test() {
+        // write well past the end of the program and read it back
+        let intcomp = run_with_input("1101,3,4,100000,4,100000,99", &[]);
+        assert_eq!(intcomp.output, [7]);
+
+        let mut intcomp: IntComputer = "1101,3,4,20,99".parse().unwrap();
+        intcomp.memory.set_limit(Some(16));
+        assert_eq!(
+            intcomp.run(),
+            Err(IntcodeError::AddressOutOfRange {
+                addr: 0,
+                op: 1101,
+                target: 20,
+                limit: 16
+            })
+        );
+    }
+
+    #[test]
+    fn error_test() {
+        let mut intcomp: IntComputer = "1,0,0,0,77".parse().unwrap();
+        assert_eq!(
+            intcomp.run(),
+            Err(IntcodeError::UnknownOpcode { addr: 4, op: 77 })
+        );
+
+        let mut intcomp: IntComputer = "204,-3,99".parse().unwrap();
+        assert_eq!(
+            intcomp.run(),
+            Err(IntcodeError::NegativeAddress {
+                addr: 0,
+                op: 204,
+                target: -3
+            })
+        );
+
+        let mut intcomp: IntComputer = "3,0,3,0,99".parse().unwrap();
+        intcomp.push_input(1);
+        assert_eq!(
+            intcomp.run_to_halt(),
+            Err(IntcodeError::InputExhausted { addr: 2, op: 3 })
+        );
+
+        let mut intcomp: IntComputer = "11101,1,2,3,99".parse().unwrap();
+        assert_eq!(
+            intcomp.run(),
+            Err(IntcodeError::InvalidMode {
+                addr: 0,
+                op: 11101,
+                param: 2,
+                mode: 1
+            })
+        );
+
+        // 2^32 + 5 must never alias cell 5, whatever the pointer width
+        let target = (1i64 << 32) + 5;
+        let mut intcomp: IntComputer = format!("4,{},99,0,0,42", target).parse().unwrap();
+        match usize::try_from(target) {
+            Ok(_) => {
+                assert_eq!(intcomp.run(), Ok(Status::ProducedOutput));
+                assert_eq!(intcomp.pop_output(), Some(0));
+            }
+            Err(_) => assert_eq!(
+                intcomp.run(),
+                Err(IntcodeError::Overflow { addr: 0, op: 4 })
+            ),
+        }
+    }
+
+    #[test]
+    fn parse_test() {
+        let intcomp = IntComputer::try_from("1,0,0,3, 99\n").unwrap();
+        assert_eq!(intcomp.memory.iter().count(), 5);
+
+        let err = "1,0,0,3;99".parse::<IntComputer>().unwrap_err();
+        assert_eq!(err.index, 3);
+        assert_eq!(err.column, 7);
+    }
+
+    #[test]
+    fn day9_test() {
+        let intcomp = run_with_input("1102,34915192,34915192,7,4,7,99,0", &[]);
+        assert_eq!(intcomp.output, [1219070632396864]);
+
+        let prog = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let intcomp = run_with_input(prog, &[]);
+        let output: Vec<String> = intcomp.output.iter().map(|v| v.to_string()).collect();
+        assert_eq!(output.join(","), prog);
+
+        let intcomp = run_with_input("109,19,204,-15,99", &[]);
+        assert_eq!(intcomp.relative_base, 19);
+        assert_eq!(intcomp.output, [99]);
+    }
+}
@@ -0,0 +1,192 @@
+use core::hash::{Hash, Hasher};
+
+use crate::{Cell, IntComputer, IntcodeError, Opcode, Result};
+
+// Guards against programs that never halt. `max_steps` bounds the total
+// number of instructions `run` executes over the machine's lifetime (see
+// `IntComputer::steps`), `detect_loops` stops a run that comes back to a
+// state it has already been in. Loop detection hashes all of memory after
+// every instruction, so it is meant for debugging rather than for speed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub max_steps: Option<u64>,
+    pub detect_loops: bool,
+}
+
+// FNV-1a, which needs nothing from std
+struct Fnv(u64);
+
+impl Hasher for Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// Brent's cycle detection over state hashes: remember the state at every
+// power of two steps and compare each following state against it. It is kept
+// on the machine, so a loop that keeps returning from `run` with output is
+// still caught. Reading input starts over, since a machine that takes input
+// is not stuck however often it comes back to the same state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LoopDetector {
+    saved: u64,
+    steps: u64,
+    power: u64,
+    low: usize,
+    high: usize,
+}
+
+impl LoopDetector {
+    pub(crate) fn new() -> LoopDetector {
+        LoopDetector {
+            saved: 0,
+            steps: 0,
+            power: 0,
+            low: usize::MAX,
+            high: 0,
+        }
+    }
+
+    // Returns the period and cursor range when `state` was seen before
+    fn check(&mut self, state: u64, cursor: usize) -> Option<(u64, usize, usize)> {
+        self.steps += 1;
+        self.low = self.low.min(cursor);
+        self.high = self.high.max(cursor);
+        if self.power > 0 && state == self.saved {
+            return Some((self.steps, self.low, self.high));
+        }
+        if self.steps >= self.power {
+            self.saved = state;
+            self.steps = 0;
+            self.power = (self.power * 2).max(1);
+            self.low = usize::MAX;
+            self.high = 0;
+        }
+        None
+    }
+}
+
+fn is_halt(op: i64) -> bool {
+    op % 100 == Opcode::Halt.code()
+}
+
+impl<C: Cell> IntComputer<C> {
+    // Fails before executing an instruction past the step budget. Halting
+    // is free, so a halted machine keeps reporting that it halted.
+    pub(crate) fn check_steps(&self) -> Result<()> {
+        match self.limits.max_steps {
+            Some(max) if self.steps >= max && !is_halt(self.get_op()) => {
+                Err(IntcodeError::StepLimitExceeded {
+                    addr: self.cursor,
+                    op: self.get_op(),
+                    steps: max,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Counts an instruction that ran, `op` being its opcode, and checks for
+    // loops when enabled
+    pub(crate) fn count_step(&mut self, op: i64) -> Result<()> {
+        if is_halt(op) {
+            return Ok(());
+        }
+        self.steps += 1;
+        if !self.limits.detect_loops {
+            return Ok(());
+        }
+        if op % 100 == Opcode::Input.code() {
+            self.detector = LoopDetector::new();
+        }
+
+        let mut hasher = Fnv(0xcbf2_9ce4_8422_2325);
+        self.cursor.hash(&mut hasher);
+        self.relative_base.hash(&mut hasher);
+        self.input.len().hash(&mut hasher);
+        self.memory.hash(&mut hasher);
+        match self.detector.check(hasher.finish(), self.cursor) {
+            Some((period, start, end)) => Err(IntcodeError::LoopDetected {
+                addr: self.cursor,
+                op: self.get_op(),
+                period,
+                start,
+                end,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{IntComputer, IntcodeError, Status};
+
+    #[test]
+    fn step_limit_test() {
+        let mut intcomp: IntComputer = "1101,1,2,20,1105,1,0".parse().unwrap();
+        intcomp.limits.max_steps = Some(5);
+        assert_eq!(
+            intcomp.run(),
+            Err(IntcodeError::StepLimitExceeded {
+                addr: 4,
+                op: 1105,
+                steps: 5
+            })
+        );
+        assert_eq!(intcomp.steps, 5);
+
+        // outputs count as well, halting does not
+        let mut intcomp: IntComputer = "104,1,104,2,99".parse().unwrap();
+        intcomp.limits.max_steps = Some(2);
+        assert_eq!(intcomp.run_to_halt(), Ok(()));
+        assert_eq!(intcomp.steps, 2);
+        assert_eq!(intcomp.run(), Ok(Status::Halted));
+        assert_eq!(intcomp.steps, 2);
+    }
+
+    #[test]
+    fn loop_detect_test() {
+        // counts down, then bounces between 7 and 10 forever
+        let mut intcomp: IntComputer = "1001,20,-1,20,1005,20,0,1006,20,10,1006,20,7,99"
+            .parse()
+            .unwrap();
+        intcomp.memory.set(20, 3).unwrap();
+        intcomp.limits.detect_loops = true;
+        let err = intcomp.run().unwrap_err();
+        match err {
+            IntcodeError::LoopDetected {
+                period, start, end, ..
+            } => assert_eq!((period, start, end), (2, 7, 10)),
+            e => panic!("unexpected {:?}", e),
+        }
+
+        // a counting loop changes memory on every pass and is not a cycle
+        let mut intcomp: IntComputer = "1001,10,-1,10,1005,10,0,99,0,0,100".parse().unwrap();
+        intcomp.limits.detect_loops = true;
+        assert_eq!(intcomp.run(), Ok(Status::Halted));
+
+        // printing the same thing forever is caught across runs
+        let mut intcomp: IntComputer = "104,1,1105,1,0".parse().unwrap();
+        intcomp.limits.detect_loops = true;
+        let err = (0..10)
+            .map(|_| intcomp.run())
+            .find_map(|r| r.err())
+            .unwrap();
+        assert!(matches!(err, IntcodeError::LoopDetected { period: 2, .. }));
+
+        // polling for input is not a loop as long as input arrives
+        let mut intcomp: IntComputer = "3,10,1005,10,0,99".parse().unwrap();
+        intcomp.limits.detect_loops = true;
+        for _ in 0..10 {
+            assert_eq!(intcomp.run(), Ok(Status::NeedsInput));
+            intcomp.push_input(1);
+        }
+    }
+}
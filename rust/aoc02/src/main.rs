@@ -1,104 +1,178 @@
+use std::env;
+use std::fs;
 use std::io::{self, Read};
 
-type Result<T> = ::std::result::Result<T, Box<dyn::std::error::Error>>;
+use aoc02::ascii::AsciiComputer;
+use aoc02::big::BigComputer;
+use aoc02::bigint::BigInt;
+use aoc02::debug::Debugger;
+use aoc02::profile::Profile;
+use aoc02::symbolic::solve_noun_verb;
+use aoc02::trace::JsonLinesTracer;
+use aoc02::{amplifier, asm, cfg, disasm, inspect, parse_program, IntComputer, Status};
 
-#[derive(Debug)]
-struct IntComputer {
-    pub program: Vec<i64>,
-    pub cursor: usize,
-}
+type Result<T> = ::std::result::Result<T, Box<dyn ::std::error::Error>>;
 
-impl IntComputer {
-    fn from_str(input: &str) -> IntComputer {
-        let prog = input.split(",").map(|s| s.parse().unwrap()).collect();
-        IntComputer {
-            program: prog,
-            cursor: 0,
-        }
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+
+    // interactive commands need stdin for themselves
+    if args.get(1).map(|s| s.as_str()) == Some("debug") {
+        let path = args.get(2).ok_or("usage: aoc02 debug <program>")?;
+        let intcomp: IntComputer = fs::read_to_string(path)?.parse()?;
+        let stdin = io::stdin();
+        Debugger::new(intcomp).repl(stdin.lock(), io::stdout())?;
+        return Ok(());
     }
-
-    fn get_op(&self) -> i64 {
-        self.program[self.cursor]
+    if args.get(1).map(|s| s.as_str()) == Some("ascii") {
+        let path = args.get(2).ok_or("usage: aoc02 ascii <program>")?;
+        let intcomp: IntComputer = fs::read_to_string(path)?.parse()?;
+        let stdin = io::stdin();
+        AsciiComputer::new(intcomp).session(stdin.lock(), io::stdout())?;
+        return Ok(());
     }
 
-    fn execute_instr(&mut self) -> Result<()> {
-        let op = self.get_op();
-        match op {
-            1 => {
-                let p1 = self.program[self.cursor + 1] as usize;
-                let p2 = self.program[self.cursor + 2] as usize;
-                let p3 = self.program[self.cursor + 3] as usize;
-                self.program[p3] = self.program[p1] + self.program[p2];
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+
+    match args.get(1).map(|s| s.as_str()) {
+        Some("amp") => {
+            let feedback = args.get(2).map(|s| s.as_str()) == Some("--feedback");
+            let phases: Vec<i64> = if feedback {
+                (5..10).collect()
+            } else {
+                (0..5).collect()
+            };
+            let intcomp: IntComputer = input.parse()?;
+            let (signal, best) = amplifier::max_signal(&intcomp, &phases, feedback)?;
+            println!("max signal: {} (phases {:?})", signal, best);
+        }
+        Some("asm") => {
+            let program = asm::assemble(&input)?;
+            println!("{}", asm::to_program_text(&program));
+        }
+        Some("big") => {
+            let mut big: BigComputer = input.parse()?;
+            for arg in &args[2..] {
+                big.push_input(arg.parse::<BigInt>()?);
             }
-            2 => {
-                let p1 = self.program[self.cursor + 1] as usize;
-                let p2 = self.program[self.cursor + 2] as usize;
-                let p3 = self.program[self.cursor + 3] as usize;
-                self.program[p3] = self.program[p1] * self.program[p2];
+            while big.run()? == Status::ProducedOutput {}
+            for v in big.output {
+                println!("output: {}", v);
             }
-            99 => (),
-            _ => {
-                println!("Error: unexpected op code {}", op);
-                return Err("unexpected op code")?;
+        }
+        Some("cfg") => {
+            let program = parse_program(&input)?;
+            print!("{}", cfg::build(&program).to_dot());
+        }
+        Some("disasm") => {
+            let program = parse_program(&input)?;
+            print!("{}", disasm::disassemble(&program));
+        }
+        Some("dump") => {
+            let intcomp: IntComputer = input.parse()?;
+            let patch = match args.get(2) {
+                Some(text) => parse_patch(text)?,
+                None => vec![],
+            };
+            let run = inspect::run_patched(&intcomp, &patch);
+            if let Err(e) = run.result {
+                println!("fault: {}", e);
             }
+            print!("{}", inspect::hexdump(&run.intcomp, 8));
         }
-        Ok(())
-    }
+        Some("memdiff") => {
+            let usage = "usage: aoc02 memdiff <addr=value,...> <addr=value,...>";
+            let first = parse_patch(args.get(2).ok_or(usage)?)?;
+            let second = parse_patch(args.get(3).ok_or(usage)?)?;
+            let intcomp: IntComputer = input.parse()?;
+            let runs = [
+                inspect::run_patched(&intcomp, &first),
+                inspect::run_patched(&intcomp, &second),
+            ];
+            for (n, run) in runs.iter().enumerate() {
+                if let Err(ref e) = run.result {
+                    println!("run {}: fault: {}", n + 1, e);
+                }
+            }
+            print!(
+                "{}",
+                inspect::format_diff(&inspect::diff(&runs[0], &runs[1]))
+            );
+        }
+        Some("trace") => {
+            let path = args
+                .get(2)
+                .ok_or("usage: aoc02 trace <out.jsonl> [input...]")?;
+            let mut intcomp: IntComputer = input.parse()?;
+            for arg in &args[3..] {
+                intcomp.push_input(arg.parse()?);
+            }
 
-    fn run(&mut self) -> Result<()> {
-        let mut op = self.program[self.cursor];
-        while op != 99 {
-            self.execute_instr()?;
-            self.cursor += 4;
-            op = self.get_op();
+            let mut tracer = JsonLinesTracer::new(io::BufWriter::new(fs::File::create(path)?));
+            while intcomp.run_traced(&mut tracer)? == Status::ProducedOutput {}
+            tracer.finish()?;
+            for v in intcomp.output {
+                println!("output: {}", v);
+            }
         }
+        Some("profile") => {
+            let mut intcomp: IntComputer = input.parse()?;
+            for arg in &args[2..] {
+                intcomp.push_input(arg.parse()?);
+            }
 
-        Ok(())
+            let mut profile = Profile::new();
+            while intcomp.run_profiled(&mut profile)? == Status::ProducedOutput {}
+            for v in intcomp.output.drain(..) {
+                println!("output: {}", v);
+            }
+            profile.write_report(&intcomp.memory, 10, io::stdout())?;
+        }
+        Some(cmd) => Err(format!("unknown command {}", cmd))?,
+        None => {
+            part1(&input)?;
+            part2(&input)?;
+        }
     }
-}
-
-fn main() -> Result<()> {
-    let mut input = String::new();
-    io::stdin().read_to_string(&mut input)?;
-
-    part1(&input)?;
-    part2(&input)?;
 
     Ok(())
 }
 
+// "1=12,2=2" stores 12 at address 1 and 2 at address 2
+fn parse_patch(text: &str) -> Result<Vec<(usize, i64)>> {
+    let mut patch = vec![];
+    for item in text.split(',').filter(|s| !s.is_empty()) {
+        let (addr, value) = item
+            .split_once('=')
+            .ok_or_else(|| format!("expected addr=value, got {:?}", item))?;
+        patch.push((addr.trim().parse()?, value.trim().parse()?));
+    }
+    Ok(patch)
+}
+
 fn part1(input: &str) -> Result<()> {
-    let mut intcomp = IntComputer::from_str(input);
+    let mut intcomp: IntComputer = input.parse()?;
 
     // reset state
-    intcomp.program[1] = 12;
-    intcomp.program[2] = 2;
+    intcomp.memory.set(1, 12)?;
+    intcomp.memory.set(2, 2)?;
 
-    intcomp.run()?;
-    println!("part 1: {}", intcomp.program[0]);
+    intcomp.run_to_halt()?;
+    println!("part 1: {}", intcomp.memory[0]);
 
     Ok(())
 }
 
 fn part2(input: &str) -> Result<()> {
     let target = 19690720;
+    let intcomp: IntComputer = input.parse()?;
 
-    for noun in 0..100 {
-        for verb in 0..100 {
-            let mut intcomp = IntComputer::from_str(input);
-
-            // reset state
-            intcomp.program[1] = noun;
-            intcomp.program[2] = verb;
-
-            intcomp.run()?;
-
-            let result = intcomp.program[0];
-            if result == target {
-                println!("Found: {}", 100 * noun + verb);
-                return Ok(());
-            }
+    match solve_noun_verb(&intcomp, target)? {
+        Some(solution) => {
+            println!("Found: {}", 100 * solution.noun + solution.verb);
+            Ok(())
         }
+        None => Err("No solution found")?,
     }
-    Err("No solution found")?
 }
@@ -0,0 +1,220 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+use core::ops::Index;
+
+use crate::Cell;
+
+// Writes that land at most this far past the end of the dense region grow it,
+// anything further away is kept in the sparse map.
+const DENSE_GAP: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitExceeded {
+    pub addr: usize,
+    pub limit: usize,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "memory limit of {} cells exceeded writing address {}",
+            self.limit, self.addr
+        )
+    }
+}
+
+impl Error for LimitExceeded {}
+
+// Intcode memory: the program image plus any cells written past its end.
+// Unwritten cells read as zero.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Memory<C = i64> {
+    cells: Vec<C>,
+    sparse: BTreeMap<usize, C>,
+    limit: Option<usize>,
+}
+
+impl<C: Cell> Memory<C> {
+    pub fn new(cells: Vec<C>) -> Memory<C> {
+        Memory {
+            cells,
+            sparse: BTreeMap::new(),
+            limit: None,
+        }
+    }
+
+    // Fails if the parts could not have come from a `Memory`: sparse cells
+    // must lie past the dense ones, and the footprint within the limit
+    #[cfg(feature = "std")]
+    pub(crate) fn from_parts(
+        cells: Vec<C>,
+        sparse: BTreeMap<usize, C>,
+        limit: Option<usize>,
+    ) -> Result<Memory<C>, String> {
+        if let Some(&addr) = sparse.keys().next() {
+            if addr < cells.len() {
+                return Err(format!(
+                    "sparse cell {} inside the {} dense cells",
+                    addr,
+                    cells.len()
+                ));
+            }
+        }
+        let memory = Memory {
+            cells,
+            sparse,
+            limit,
+        };
+        match limit {
+            Some(limit) if memory.footprint() > limit => Err(format!(
+                "{} cells exceed the limit of {}",
+                memory.footprint(),
+                limit
+            )),
+            _ => Ok(memory),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn dense(&self) -> &[C] {
+        &self.cells
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn sparse(&self) -> &BTreeMap<usize, C> {
+        &self.sparse
+    }
+
+    // Maximum number of cells that may be allocated, None for no limit
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    // Number of cells currently backed by storage
+    pub fn footprint(&self) -> usize {
+        self.cells.len() + self.sparse.len()
+    }
+
+    // One past the highest allocated address
+    pub fn len(&self) -> usize {
+        match self.sparse.keys().next_back() {
+            Some(&addr) => addr + 1,
+            None => self.cells.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, addr: usize) -> C {
+        self[addr].clone()
+    }
+
+    pub fn set(&mut self, addr: usize, value: C) -> Result<(), LimitExceeded> {
+        if addr < self.cells.len() {
+            self.cells[addr] = value;
+            return Ok(());
+        }
+        if let Some(cell) = self.sparse.get_mut(&addr) {
+            *cell = value;
+            return Ok(());
+        }
+
+        if addr - self.cells.len() <= DENSE_GAP {
+            let migrated = self.sparse.range(..=addr).count();
+            let grown = addr + 1 - self.cells.len() - migrated;
+            self.check_limit(addr, grown)?;
+
+            self.cells.resize(addr + 1, C::default());
+            let rest = self.sparse.split_off(&(addr + 1));
+            for (a, v) in core::mem::replace(&mut self.sparse, rest) {
+                self.cells[a] = v;
+            }
+            self.cells[addr] = value;
+        } else {
+            self.check_limit(addr, 1)?;
+            self.sparse.insert(addr, value);
+        }
+        Ok(())
+    }
+
+    fn check_limit(&self, addr: usize, grown: usize) -> Result<(), LimitExceeded> {
+        match self.limit {
+            Some(limit) if self.footprint() + grown > limit => Err(LimitExceeded { addr, limit }),
+            _ => Ok(()),
+        }
+    }
+
+    // All allocated cells in address order
+    pub fn iter(&self) -> impl Iterator<Item = (usize, C)> + '_ {
+        self.cells
+            .iter()
+            .cloned()
+            .enumerate()
+            .chain(self.sparse.iter().map(|(&a, v)| (a, v.clone())))
+    }
+}
+
+impl<C: Cell> Index<usize> for Memory<C> {
+    type Output = C;
+
+    fn index(&self, addr: usize) -> &C {
+        match self.cells.get(addr) {
+            Some(v) => v,
+            None => self.sparse.get(&addr).unwrap_or_else(|| C::zero()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grow_test() {
+        let mut mem: Memory = Memory::new(vec![1, 2, 3]);
+        assert_eq!(mem[10], 0);
+
+        mem.set(10, 7).unwrap();
+        assert_eq!(mem[10], 7);
+        assert_eq!(mem.footprint(), 11);
+
+        mem.set(1_000_000, 5).unwrap();
+        assert_eq!(mem[1_000_000], 5);
+        assert_eq!(mem.footprint(), 12);
+        assert_eq!(mem.len(), 1_000_001);
+    }
+
+    #[test]
+    fn migrate_test() {
+        let mut mem: Memory = Memory::new(vec![]);
+        mem.set(5000, 1).unwrap();
+        mem.set(5001, 2).unwrap();
+        mem.set(8000, 3).unwrap();
+
+        // growing the dense region over sparse cells keeps their values
+        mem.set(4090, 4).unwrap();
+        mem.set(5002, 9).unwrap();
+        assert_eq!((mem[5000], mem[5001], mem[5002], mem[8000]), (1, 2, 9, 3));
+        assert_eq!(mem.footprint(), 5004);
+        assert_eq!(mem.iter().filter(|&(_, v)| v != 0).count(), 5);
+    }
+
+    #[test]
+    fn limit_test() {
+        let mut mem: Memory = Memory::new(vec![0; 4]);
+        mem.set_limit(Some(8));
+        mem.set(7, 1).unwrap();
+        assert_eq!(mem.set(8, 1), Err(LimitExceeded { addr: 8, limit: 8 }));
+        assert!(mem.set(100_000, 1).is_err());
+        assert_eq!(mem.footprint(), 8);
+    }
+}
@@ -0,0 +1,412 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::ops::ControlFlow;
+
+use crate::{IntComputer, Result, Status};
+
+// A machine sends a packet by producing three outputs: destination, x, y.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub dest: i64,
+    pub x: i64,
+    pub y: i64,
+}
+
+// Hooks for the router logic. `external` receives every packet addressed
+// outside the network, `idle` is called once no machine has sent or received
+// anything for a whole round and may queue packets to wake the network up.
+// Returning `ControlFlow::Break` from either hook ends `Network::run`.
+pub trait Router {
+    type Output;
+
+    fn observe(&mut self, _packet: &Packet) {}
+
+    fn external(&mut self, packet: Packet) -> ControlFlow<Self::Output>;
+
+    fn idle(&mut self, inject: &mut Vec<Packet>) -> ControlFlow<Self::Output>;
+}
+
+pub struct Network {
+    pub machines: Vec<IntComputer>,
+    queues: Vec<VecDeque<Packet>>,
+    // outputs of a packet still being sent
+    partial: Vec<Vec<i64>>,
+    halted: Vec<bool>,
+    pub idle_count: usize,
+}
+
+impl Network {
+    // Boot `size` copies of `program`, each receiving its address as its
+    // first input.
+    pub fn new(program: &IntComputer, size: usize) -> Network {
+        let machines = (0..size)
+            .map(|addr| {
+                let mut intcomp = program.clone();
+                intcomp.push_input(addr as i64);
+                intcomp
+            })
+            .collect();
+        Network {
+            machines,
+            queues: vec![VecDeque::new(); size],
+            partial: vec![vec![]; size],
+            halted: vec![false; size],
+            idle_count: 0,
+        }
+    }
+
+    pub fn pending(&self, addr: usize) -> usize {
+        self.queues[addr].len()
+    }
+
+    // Values of a packet the machine has started but not finished sending.
+    // A machine can be interrupted mid-packet when it waits for input.
+    pub fn partial(&self, addr: usize) -> &[i64] {
+        &self.partial[addr]
+    }
+
+    fn deliver<R: Router>(&mut self, packet: Packet, router: &mut R) -> ControlFlow<R::Output> {
+        router.observe(&packet);
+        let dest = usize::try_from(packet.dest).ok();
+        if let Some(dest) = dest.filter(|&d| d < self.machines.len()) {
+            // nobody is left to read it at a halted machine
+            if !self.halted[dest] {
+                self.queues[dest].push_back(packet);
+            }
+            ControlFlow::Continue(())
+        } else {
+            router.external(packet)
+        }
+    }
+
+    // Give one machine its next packet (or -1 when there is none) and run
+    // it until it asks for more input. Returns whether it received a packet
+    // and what it sent.
+    fn run_slice(&mut self, addr: usize) -> Result<(bool, Vec<Packet>)> {
+        let intcomp = &mut self.machines[addr];
+        let received = match self.queues[addr].pop_front() {
+            Some(packet) => {
+                intcomp.push_input(packet.x);
+                intcomp.push_input(packet.y);
+                true
+            }
+            None => {
+                intcomp.push_input(-1);
+                false
+            }
+        };
+
+        let mut sent = vec![];
+        loop {
+            let status = intcomp.run()?;
+            let partial = &mut self.partial[addr];
+            partial.extend(intcomp.output.drain(..));
+            while partial.len() >= 3 {
+                let (dest, x, y) = (partial[0], partial[1], partial[2]);
+                partial.drain(..3);
+                sent.push(Packet { dest, x, y });
+            }
+            match status {
+                Status::ProducedOutput => continue,
+                Status::NeedsInput => break,
+                Status::Halted => {
+                    self.halted[addr] = true;
+                    break;
+                }
+            }
+        }
+        Ok((received, sent))
+    }
+
+    // Round-robin the machines until the router stops the network. Returns
+    // None if every machine has halted, or the network is idle and the
+    // router has nothing to inject.
+    pub fn run<R: Router>(&mut self, router: &mut R) -> Result<Option<R::Output>> {
+        loop {
+            let mut active = false;
+            for addr in 0..self.machines.len() {
+                if self.halted[addr] {
+                    continue;
+                }
+                let (received, sent) = self.run_slice(addr)?;
+                active |= received || !sent.is_empty();
+                for packet in sent {
+                    if let ControlFlow::Break(out) = self.deliver(packet, router) {
+                        return Ok(Some(out));
+                    }
+                }
+            }
+
+            if self.halted.iter().all(|&h| h) {
+                return Ok(None);
+            }
+            let pending = (0..self.machines.len()).any(|a| !self.halted[a] && self.pending(a) > 0);
+            if active || pending {
+                continue;
+            }
+
+            self.idle_count += 1;
+            let mut inject = vec![];
+            if let ControlFlow::Break(out) = router.idle(&mut inject) {
+                return Ok(Some(out));
+            }
+            if inject.is_empty() {
+                return Ok(None);
+            }
+            for packet in inject {
+                if let ControlFlow::Break(out) = self.deliver(packet, router) {
+                    return Ok(Some(out));
+                }
+            }
+        }
+    }
+}
+
+// Monitor living at network address `addr` (255 in the puzzle). It keeps the
+// last packet sent to it and, whenever the network goes idle, resends that
+// packet to address 0. It stops the network once it sends the same y value
+// twice in a row, returning that value. Packets to any other address outside
+// the network are dropped.
+#[derive(Debug)]
+pub struct Nat {
+    pub addr: i64,
+    pub first: Option<Packet>,
+    pub last: Option<Packet>,
+    last_sent_y: Option<i64>,
+}
+
+impl Nat {
+    pub fn new(addr: i64) -> Nat {
+        Nat {
+            addr,
+            first: None,
+            last: None,
+            last_sent_y: None,
+        }
+    }
+}
+
+impl Router for Nat {
+    type Output = i64;
+
+    fn external(&mut self, packet: Packet) -> ControlFlow<i64> {
+        if packet.dest != self.addr {
+            return ControlFlow::Continue(());
+        }
+        if self.first.is_none() {
+            self.first = Some(packet);
+        }
+        self.last = Some(packet);
+        ControlFlow::Continue(())
+    }
+
+    fn idle(&mut self, inject: &mut Vec<Packet>) -> ControlFlow<i64> {
+        if let Some(packet) = self.last {
+            if self.last_sent_y == Some(packet.y) {
+                return ControlFlow::Break(packet.y);
+            }
+            self.last_sent_y = Some(packet.y);
+            inject.push(Packet { dest: 0, ..packet });
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    // Node 0 starts by sending (1, 100, 0). Every node forwards the packets
+    // it receives to the next address, adding its own address to y.
+    const NODE: &str = "
+                in    [addr]
+                jz    [addr], start
+        loop:   in    [x]
+                eq    [x], -1, [tmp]
+                jnz   [tmp], loop
+                in    [y]
+                add   [y], [addr], [y]
+                add   [addr], 1, [dest]
+                out   [dest]
+                out   [x]
+                out   [y]
+                jnz   1, loop
+        start:  out   1
+                out   100
+                out   0
+                jnz   1, loop
+        addr:   .data 0
+        x:      .data 0
+        y:      .data 0
+        dest:   .data 0
+        tmp:    .data 0
+    ";
+
+    fn node() -> IntComputer {
+        IntComputer::new(assemble(NODE).unwrap())
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        external: Vec<Packet>,
+        observed: usize,
+        idle_calls: usize,
+    }
+
+    impl Router for Recorder {
+        type Output = usize;
+
+        fn observe(&mut self, _packet: &Packet) {
+            self.observed += 1;
+        }
+
+        fn external(&mut self, packet: Packet) -> ControlFlow<usize> {
+            self.external.push(packet);
+            ControlFlow::Continue(())
+        }
+
+        fn idle(&mut self, inject: &mut Vec<Packet>) -> ControlFlow<usize> {
+            self.idle_calls += 1;
+            if self.idle_calls == 2 {
+                return ControlFlow::Break(self.external.len());
+            }
+            inject.push(Packet {
+                dest: 0,
+                x: 7,
+                y: 10,
+            });
+            ControlFlow::Continue(())
+        }
+    }
+
+    #[test]
+    fn route_test() {
+        let mut network = Network::new(&node(), 3);
+        let mut router = Recorder::default();
+        assert_eq!(network.run(&mut router).unwrap(), Some(2));
+
+        assert_eq!(network.idle_count, 2);
+        assert_eq!(
+            router.external,
+            [
+                Packet {
+                    dest: 3,
+                    x: 100,
+                    y: 3
+                },
+                Packet {
+                    dest: 3,
+                    x: 7,
+                    y: 13
+                },
+            ]
+        );
+        // 3 hops per trip plus the injected packet
+        assert_eq!(router.observed, 3 + 1 + 3);
+    }
+
+    #[test]
+    fn nat_test() {
+        // node 0 reports to the monitor once, then swallows everything
+        let source = "
+                    in    [addr]
+                    jnz   [addr], loop
+                    out   255
+                    out   1
+                    out   42
+            loop:   in    [x]
+                    jnz   1, loop
+            addr:   .data 0
+            x:      .data 0
+        ";
+        let program = IntComputer::new(assemble(source).unwrap());
+        let mut network = Network::new(&program, 2);
+        let mut nat = Nat::new(255);
+        assert_eq!(network.run(&mut nat).unwrap(), Some(42));
+        assert_eq!(nat.first.map(|p| p.dest), Some(255));
+        assert_eq!(network.idle_count, 2);
+
+        // a monitor somewhere else never hears from node 0
+        let mut network = Network::new(&program, 2);
+        let mut nat = Nat::new(100);
+        assert_eq!(network.run(&mut nat).unwrap(), None);
+        assert_eq!(nat.first, None);
+    }
+
+    #[test]
+    fn halted_dest_test() {
+        // node 0 halts at boot, node 1 sends it a packet and then waits
+        let source = "
+                    in    [addr]
+                    jz    [addr], done
+                    out   0
+                    out   5
+                    out   6
+            wait:   in    [x]
+                    jnz   1, wait
+            done:   hlt
+            addr:   .data 0
+            x:      .data 0
+        ";
+        let program = IntComputer::new(assemble(source).unwrap());
+        let mut network = Network::new(&program, 2);
+        let mut router = Recorder::default();
+        // the router's packets to node 0 are dropped as well
+        assert_eq!(network.run(&mut router).unwrap(), Some(0));
+        assert_eq!(router.observed, 2);
+        assert_eq!(network.pending(0), 0);
+    }
+
+    #[test]
+    fn partial_packet_test() {
+        // node 0 sends the destination, blocks on input, then sends the
+        // rest; node 1 forwards what it gets to address 5
+        let source = "
+                    in    [addr]
+                    jnz   [addr], relay
+                    out   1
+                    in    [x]
+                    in    [x]
+                    out   7
+                    out   8
+            wait:   in    [x]
+                    jnz   1, wait
+            relay:  in    [x]
+                    eq    [x], -1, [tmp]
+                    jnz   [tmp], relay
+                    in    [y]
+                    out   5
+                    out   [x]
+                    out   [y]
+                    jnz   1, relay
+            addr:   .data 0
+            x:      .data 0
+            y:      .data 0
+            tmp:    .data 0
+        ";
+        let program = IntComputer::new(assemble(source).unwrap());
+        let mut network = Network::new(&program, 2);
+        let mut router = Recorder::default();
+        network.run_slice(0).unwrap();
+        assert_eq!(network.partial(0), [1]);
+        network.run(&mut router).unwrap();
+        assert_eq!(network.partial(0), []);
+        assert_eq!(
+            router.external,
+            [Packet {
+                dest: 5,
+                x: 7,
+                y: 8
+            }]
+        );
+    }
+
+    #[test]
+    fn deadlock_test() {
+        // nobody ever talks to the monitor, so idling cannot be resolved
+        let program: IntComputer = "3,100,3,100,1105,1,2".parse().unwrap();
+        let mut network = Network::new(&program, 2);
+        assert_eq!(network.run(&mut Nat::new(255)).unwrap(), None);
+    }
+}
@@ -0,0 +1,174 @@
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+use core::str::FromStr;
+
+// Program text is a comma separated list of integers. Whitespace (including
+// newlines) may appear anywhere between tokens and `#` starts a comment that
+// runs to the end of the line.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    Empty,
+    InvalidNumber(String),
+    MissingValue,
+    MissingComma,
+}
+
+// `index` is the position of the offending value in the program, `line` and
+// `column` are 1-based and point at the first character of the problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub index: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ParseErrorKind::Empty => return write!(f, "empty program"),
+            ParseErrorKind::InvalidNumber(ref token) => write!(f, "invalid number {:?}", token)?,
+            ParseErrorKind::MissingValue => write!(f, "missing value")?,
+            ParseErrorKind::MissingComma => write!(f, "missing comma")?,
+        }
+        write!(
+            f,
+            " at token {} (line {}, column {})",
+            self.index, self.line, self.column
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+enum Token<'a> {
+    Value(&'a str),
+    Comma,
+}
+
+fn tokenize(input: &str) -> Vec<(Token<'_>, usize, usize)> {
+    let mut tokens = vec![];
+    for (lineno, line) in input.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(pos) => &line[..pos],
+            None => line,
+        };
+
+        let mut start = None;
+        for (pos, c) in line.char_indices().chain(Some((line.len(), ' '))) {
+            if c.is_whitespace() || c == ',' {
+                if let Some(s) = start.take() {
+                    let column = line[..s].chars().count() + 1;
+                    tokens.push((Token::Value(&line[s..pos]), lineno + 1, column));
+                }
+                if c == ',' {
+                    let column = line[..pos].chars().count() + 1;
+                    tokens.push((Token::Comma, lineno + 1, column));
+                }
+            } else if start.is_none() {
+                start = Some(pos);
+            }
+        }
+    }
+    tokens
+}
+
+pub fn parse_program(input: &str) -> Result<Vec<i64>, ParseError> {
+    parse_values(input)
+}
+
+// Same syntax for any integer type
+pub(crate) fn parse_values<T: FromStr>(input: &str) -> Result<Vec<T>, ParseError> {
+    let mut program = vec![];
+    let mut expect_value = true;
+    let mut last = (1, 1);
+
+    for (token, line, column) in tokenize(input) {
+        let err = |kind| ParseError {
+            kind,
+            index: program.len(),
+            line,
+            column,
+        };
+        match token {
+            Token::Value(text) => {
+                if !expect_value {
+                    return Err(err(ParseErrorKind::MissingComma));
+                }
+                let value = text
+                    .parse()
+                    .map_err(|_| err(ParseErrorKind::InvalidNumber(text.to_string())))?;
+                program.push(value);
+                expect_value = false;
+            }
+            Token::Comma => {
+                if expect_value {
+                    return Err(err(ParseErrorKind::MissingValue));
+                }
+                expect_value = true;
+            }
+        }
+        last = (line, column + 1);
+    }
+
+    if program.is_empty() {
+        return Err(ParseError {
+            kind: ParseErrorKind::Empty,
+            index: 0,
+            line: last.0,
+            column: last.1,
+        });
+    }
+    if expect_value {
+        return Err(ParseError {
+            kind: ParseErrorKind::MissingValue,
+            index: program.len(),
+            line: last.0,
+            column: last.1,
+        });
+    }
+
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(parse_program("1,0,0,3,99\n").unwrap(), [1, 0, 0, 3, 99]);
+
+        let input = "# header comment\n1, 9, 10, 3,\n  2,3,11,0, # tail\n99\n";
+        assert_eq!(
+            parse_program(input).unwrap(),
+            [1, 9, 10, 3, 2, 3, 11, 0, 99]
+        );
+    }
+
+    #[test]
+    fn parse_error_test() {
+        let err = parse_program("1,0,x,3").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidNumber("x".to_string()));
+        assert_eq!((err.index, err.line, err.column), (2, 1, 5));
+
+        let err = parse_program("1,0,\n3,,99").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::MissingValue);
+        assert_eq!((err.index, err.line, err.column), (3, 2, 3));
+
+        let err = parse_program("1,0 3").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::MissingComma);
+        assert_eq!((err.index, err.line, err.column), (2, 1, 5));
+
+        let err = parse_program("1,2,").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::MissingValue);
+        assert_eq!((err.index, err.line, err.column), (2, 1, 5));
+
+        let err = parse_program(" # nothing here\n").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::Empty);
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::disasm::disassemble_one;
+use crate::trace::{TraceEntry, Tracer};
+use crate::{IntComputer, Memory, Opcode, Result, Status};
+
+// A backward transfer of control from `tail` to `head`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Loop {
+    pub head: usize,
+    pub tail: usize,
+    pub iterations: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub steps: u64,
+    pub elapsed: Duration,
+    per_opcode: [u64; 10],
+    per_addr: BTreeMap<usize, u64>,
+    loops: BTreeMap<(usize, usize), u64>,
+    last: Option<usize>,
+}
+
+impl Tracer for Profile {
+    fn trace(&mut self, entry: &TraceEntry) {
+        self.steps += 1;
+        let index = Opcode::ALL.iter().position(|&o| o == entry.opcode);
+        self.per_opcode[index.unwrap()] += 1;
+        *self.per_addr.entry(entry.cursor).or_insert(0) += 1;
+        if let Some(last) = self.last {
+            if entry.cursor <= last {
+                *self.loops.entry((entry.cursor, last)).or_insert(0) += 1;
+            }
+        }
+        self.last = Some(entry.cursor);
+    }
+}
+
+impl Profile {
+    pub fn new() -> Profile {
+        Profile::default()
+    }
+
+    pub fn opcode_count(&self, opcode: Opcode) -> u64 {
+        let index = Opcode::ALL.iter().position(|&o| o == opcode);
+        self.per_opcode[index.unwrap()]
+    }
+
+    pub fn addr_count(&self, addr: usize) -> u64 {
+        self.per_addr.get(&addr).copied().unwrap_or(0)
+    }
+
+    // The `n` most executed addresses, ties broken by address
+    pub fn hot_addresses(&self, n: usize) -> Vec<(usize, u64)> {
+        let mut hot: Vec<(usize, u64)> = self.per_addr.iter().map(|(&a, &c)| (a, c)).collect();
+        hot.sort_by_key(|&(addr, count)| (std::cmp::Reverse(count), addr));
+        hot.truncate(n);
+        hot
+    }
+
+    pub fn hot_loops(&self, n: usize) -> Vec<Loop> {
+        let mut loops: Vec<Loop> = self
+            .loops
+            .iter()
+            .map(|(&(head, tail), &iterations)| Loop {
+                head,
+                tail,
+                iterations,
+            })
+            .collect();
+        loops.sort_by_key(|l| (std::cmp::Reverse(l.iterations), l.head, l.tail));
+        loops.truncate(n);
+        loops
+    }
+
+    pub fn write_report<W: Write>(
+        &self,
+        memory: &Memory,
+        top: usize,
+        mut out: W,
+    ) -> io::Result<()> {
+        let secs = self.elapsed.as_secs_f64();
+        let rate = if secs > 0.0 {
+            self.steps as f64 / secs
+        } else {
+            0.0
+        };
+        writeln!(
+            out,
+            "{} steps in {:.3?} ({:.0} steps/s)",
+            self.steps, self.elapsed, rate
+        )?;
+
+        writeln!(out, "\nopcodes:")?;
+        for &opcode in Opcode::ALL.iter() {
+            let count = self.opcode_count(opcode);
+            if count > 0 {
+                writeln!(out, "{:>6} {:>12}", opcode.mnemonic(), count)?;
+            }
+        }
+
+        writeln!(out, "\nhot addresses:")?;
+        for (addr, count) in self.hot_addresses(top) {
+            let (text, _) = disassemble_one(memory, addr);
+            writeln!(out, "{:>6} {:>12}    {}", addr, count, text)?;
+        }
+
+        writeln!(out, "\nhot loops:")?;
+        for l in self.hot_loops(top) {
+            writeln!(
+                out,
+                "{:>6}..{:<6} {:>12} iterations",
+                l.head, l.tail, l.iterations
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl IntComputer {
+    // Same as `run_traced`, also adding the wall time spent to the profile
+    pub fn run_profiled(&mut self, profile: &mut Profile) -> Result<Status> {
+        let start = Instant::now();
+        let result = self.run_traced(profile);
+        profile.elapsed += start.elapsed();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_test() {
+        // count down from 5 in a two instruction loop
+        let mut intcomp: IntComputer = "1001,10,-1,10,1005,10,0,99,0,0,5".parse().unwrap();
+        let mut profile = Profile::new();
+        assert_eq!(intcomp.run_profiled(&mut profile), Ok(Status::Halted));
+
+        assert_eq!(profile.steps, 5 * 2 + 1);
+        assert_eq!(profile.opcode_count(Opcode::Add), 5);
+        assert_eq!(profile.opcode_count(Opcode::JumpIfTrue), 5);
+        assert_eq!(profile.opcode_count(Opcode::Halt), 1);
+        assert_eq!(profile.addr_count(4), 5);
+        assert_eq!(profile.hot_addresses(2), [(0, 5), (4, 5)]);
+        assert_eq!(
+            profile.hot_loops(5),
+            [Loop {
+                head: 0,
+                tail: 4,
+                iterations: 4
+            }]
+        );
+    }
+
+    #[test]
+    fn report_test() {
+        let mut intcomp: IntComputer = "1001,10,-1,10,1005,10,0,99,0,0,5".parse().unwrap();
+        let mut profile = Profile::new();
+        intcomp.run_profiled(&mut profile).unwrap();
+
+        let mut out = vec![];
+        profile.write_report(&intcomp.memory, 1, &mut out).unwrap();
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.starts_with("11 steps in "));
+        assert!(report.contains("\n   add            5\n"));
+        assert!(report.contains("\n     0            5    add   [10], -1, [10]\n"));
+        assert!(report.contains("\n     0..4                 4 iterations\n"));
+    }
+}
@@ -0,0 +1,185 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::{IntComputer, IntcodeError, Result};
+
+// Values stored into memory before a candidate run
+pub type Patch = Vec<(usize, i64)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Find {
+    First,
+    All,
+}
+
+// Every combination of values for the given cells, the last cell varying
+// fastest. `sweep(&[(1, 0..100), (2, 0..100)])` is the noun/verb search space.
+pub fn sweep(cells: &[(usize, Range<i64>)]) -> Vec<Patch> {
+    let mut patches = vec![vec![]];
+    for (addr, range) in cells {
+        let addr = *addr;
+        patches = patches
+            .into_iter()
+            .flat_map(|patch: Patch| {
+                range.clone().map(move |value| {
+                    let mut patch = patch.clone();
+                    patch.push((addr, value));
+                    patch
+                })
+            })
+            .collect();
+    }
+    patches
+}
+
+fn run_patch(intcomp: &mut IntComputer, patch: &Patch) -> Result<()> {
+    for &(addr, value) in patch {
+        intcomp.store(addr, value)?;
+    }
+    intcomp.run_to_halt()
+}
+
+// Run `program` once per patch on `threads` workers (0 uses every available
+// core) and return the patches whose final state satisfies `predicate`, in
+// the order they were given. Workers take candidates in order, so with
+// `Find::First` the result is the same match a sequential loop would find,
+// and nothing past it is run once it is known. A failing run stops a
+// `Find::First` search unless an earlier candidate already matched; with
+// `Find::All` it is skipped as a candidate that does not match.
+pub fn search<F>(
+    program: &IntComputer,
+    patches: &[Patch],
+    threads: usize,
+    find: Find,
+    predicate: F,
+) -> Result<Vec<Patch>>
+where
+    F: Fn(&IntComputer) -> bool + Sync,
+{
+    let threads = match threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    let next = AtomicUsize::new(0);
+    let first_match = AtomicUsize::new(usize::MAX);
+    let failed = AtomicBool::new(false);
+    let matches = Mutex::new(vec![]);
+    let error: Mutex<Option<(usize, IntcodeError)>> = Mutex::new(None);
+
+    let worker = || {
+        let initial = program.snapshot();
+        let mut intcomp = program.clone();
+        loop {
+            let index = next.fetch_add(1, Ordering::Relaxed);
+            if index >= patches.len()
+                || failed.load(Ordering::Relaxed)
+                || (find == Find::First && index > first_match.load(Ordering::Relaxed))
+            {
+                break;
+            }
+
+            intcomp.restore(&initial);
+            match run_patch(&mut intcomp, &patches[index]) {
+                Ok(()) if predicate(&intcomp) => {
+                    matches.lock().unwrap().push(index);
+                    first_match.fetch_min(index, Ordering::Relaxed);
+                }
+                Ok(()) => (),
+                Err(_) if find == Find::All => (),
+                Err(e) => {
+                    failed.store(true, Ordering::Relaxed);
+                    let mut error = error.lock().unwrap();
+                    if error.as_ref().is_none_or(|&(i, _)| index < i) {
+                        *error = Some((index, e));
+                    }
+                }
+            }
+        }
+    };
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(worker);
+        }
+    });
+
+    let mut matches = matches.into_inner().unwrap();
+    matches.sort_unstable();
+    if let Some((index, e)) = error.into_inner().unwrap() {
+        if matches.first().is_none_or(|&m| index < m) {
+            return Err(e);
+        }
+    }
+    if find == Find::First {
+        matches.truncate(1);
+    }
+    Ok(matches.into_iter().map(|i| patches[i].clone()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep_test() {
+        assert_eq!(
+            sweep(&[(1, 0..2), (2, 5..7)]),
+            [
+                [(1, 0), (2, 5)],
+                [(1, 0), (2, 6)],
+                [(1, 1), (2, 5)],
+                [(1, 1), (2, 6)]
+            ]
+        );
+        assert_eq!(sweep(&[(1, 0..0), (2, 0..3)]).len(), 0);
+    }
+
+    #[test]
+    fn first_test() {
+        // [0] = [1] * 100 + [2]
+        let program: IntComputer = "1,0,0,3,1002,1,100,3,1,3,2,0,99".parse().unwrap();
+        let patches = sweep(&[(1, 0..100), (2, 0..100)]);
+        for threads in 1..5 {
+            let found = search(&program, &patches, threads, Find::First, |ic| {
+                ic.memory[0] == 1234
+            });
+            assert_eq!(found, Ok(vec![vec![(1, 12), (2, 34)]]));
+        }
+
+        let found = search(&program, &patches, 0, Find::First, |_| false);
+        assert_eq!(found, Ok(vec![]));
+    }
+
+    #[test]
+    fn all_test() {
+        // [0] = [1] + [2]
+        let program: IntComputer = "1,0,0,3,1,1,2,0,99".parse().unwrap();
+        let patches = sweep(&[(1, 0..10), (2, 0..10)]);
+        let found = search(&program, &patches, 3, Find::All, |ic| ic.memory[0] == 3).unwrap();
+        assert_eq!(
+            found,
+            [
+                [(1, 0), (2, 3)],
+                [(1, 1), (2, 2)],
+                [(1, 2), (2, 1)],
+                [(1, 3), (2, 0)]
+            ]
+        );
+    }
+
+    #[test]
+    fn error_test() {
+        // opcodes 1 and 2 run, 77 faults
+        let program: IntComputer = "1,0,0,0,99".parse().unwrap();
+        let patches = vec![vec![(0, 1)], vec![(0, 2)], vec![(0, 77)]];
+        let found = search(&program, &patches, 2, Find::First, |ic| ic.memory[0] == 4);
+        assert_eq!(found, Ok(vec![vec![(0, 2)]]));
+
+        let found = search(&program, &patches, 2, Find::All, |_| true);
+        assert_eq!(found, Ok(vec![vec![(0, 1)], vec![(0, 2)]]));
+
+        let found = search(&program, &patches, 2, Find::First, |_| false);
+        assert_eq!(found, Err(IntcodeError::UnknownOpcode { addr: 0, op: 77 }));
+    }
+}
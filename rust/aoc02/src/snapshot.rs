@@ -0,0 +1,267 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::{Arithmetic, IntComputer, Memory};
+
+const HEADER: &str = "intcode-snapshot 1";
+
+// Full machine state. Taking and restoring a snapshot is just a copy, so
+// searches can fork many runs from one common starting point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    state: IntComputer,
+}
+
+impl IntComputer {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            state: self.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.clone_from(&snapshot.state);
+    }
+}
+
+fn join<T: ToString>(values: impl Iterator<Item = T>) -> String {
+    values.map(|v| v.to_string()).collect::<Vec<_>>().join(",")
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn parse_list<T: FromStr>(text: &str) -> io::Result<Vec<T>> {
+    if text.is_empty() {
+        return Ok(vec![]);
+    }
+    text.split(',')
+        .map(|v| {
+            v.parse()
+                .map_err(|_| invalid(format!("invalid value {:?}", v)))
+        })
+        .collect()
+}
+
+// Text format, one `key value` pair per line:
+//
+//     intcode-snapshot 1
+//     cursor 4
+//     relative_base 0
+//     limit -
+//     input 1,2
+//     output
+//     cells 1,0,0,3,99
+//     sparse 5000=7
+//     steps 12
+//     max_steps -
+//     detect_loops false
+//     arithmetic checked
+//
+// The last four lines may be missing from older files.
+impl Snapshot {
+    pub fn save<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let state = &self.state;
+        let limit = match state.memory.limit() {
+            Some(limit) => limit.to_string(),
+            None => "-".to_string(),
+        };
+        let sparse = state
+            .memory
+            .sparse()
+            .iter()
+            .map(|(a, v)| format!("{}={}", a, v));
+
+        writeln!(out, "{}", HEADER)?;
+        writeln!(out, "cursor {}", state.cursor)?;
+        writeln!(out, "relative_base {}", state.relative_base)?;
+        writeln!(out, "limit {}", limit)?;
+        writeln!(out, "input {}", join(state.input.iter()))?;
+        writeln!(out, "output {}", join(state.output.iter()))?;
+        writeln!(out, "cells {}", join(state.memory.dense().iter()))?;
+        writeln!(out, "sparse {}", join(sparse))?;
+        writeln!(out, "steps {}", state.steps)?;
+        match state.limits.max_steps {
+            Some(max) => writeln!(out, "max_steps {}", max)?,
+            None => writeln!(out, "max_steps -")?,
+        }
+        writeln!(out, "detect_loops {}", state.limits.detect_loops)?;
+        let arithmetic = match state.arithmetic {
+            Arithmetic::Checked => "checked",
+            Arithmetic::Wrapping => "wrapping",
+        };
+        writeln!(out, "arithmetic {}", arithmetic)?;
+        out.flush()
+    }
+
+    pub fn load<R: BufRead>(input: R) -> io::Result<Snapshot> {
+        let mut lines = input.lines();
+        let header = lines.next().transpose()?;
+        if header.as_ref().map(|h| h.trim_end()) != Some(HEADER) {
+            return Err(invalid("not an intcode snapshot".to_string()));
+        }
+
+        let mut fields = BTreeMap::new();
+        for line in lines {
+            let line = line?;
+            let line = line.trim_end();
+            let (key, value) = match line.find(' ') {
+                Some(pos) => (&line[..pos], &line[pos + 1..]),
+                None => (line, ""),
+            };
+            fields.insert(key.to_string(), value.to_string());
+        }
+        let field = |key: &str| {
+            fields
+                .get(key)
+                .map(|s| s.as_str())
+                .ok_or_else(|| invalid(format!("missing field {}", key)))
+        };
+        let number = |key: &str| {
+            let value = field(key)?;
+            value
+                .parse::<i64>()
+                .map_err(|_| invalid(format!("invalid {} {:?}", key, value)))
+        };
+
+        let limit = match field("limit")? {
+            "-" => None,
+            v => Some(
+                v.parse()
+                    .map_err(|_| invalid(format!("invalid limit {:?}", v)))?,
+            ),
+        };
+        let mut sparse = BTreeMap::new();
+        for pair in parse_list::<String>(field("sparse")?)? {
+            let mut parts = pair.splitn(2, '=');
+            let (addr, value) = (parts.next().unwrap(), parts.next().unwrap_or(""));
+            match (addr.parse(), value.parse()) {
+                (Ok(addr), Ok(value)) => sparse.insert(addr, value),
+                _ => return Err(invalid(format!("invalid sparse cell {:?}", pair))),
+            };
+        }
+        let cursor = number("cursor")?;
+        let cursor =
+            usize::try_from(cursor).map_err(|_| invalid(format!("invalid cursor {}", cursor)))?;
+
+        let optional = |key: &str| -> io::Result<Option<u64>> {
+            match fields.get(key).map(|s| s.as_str()) {
+                None | Some("-") => Ok(None),
+                Some(v) => v
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| invalid(format!("invalid {} {:?}", key, v))),
+            }
+        };
+        let detect_loops = match fields.get("detect_loops").map(|s| s.as_str()) {
+            None | Some("false") => false,
+            Some("true") => true,
+            Some(v) => return Err(invalid(format!("invalid detect_loops {:?}", v))),
+        };
+        let arithmetic = match fields.get("arithmetic").map(|s| s.as_str()) {
+            None | Some("checked") => Arithmetic::Checked,
+            Some("wrapping") => Arithmetic::Wrapping,
+            Some(v) => return Err(invalid(format!("invalid arithmetic {:?}", v))),
+        };
+
+        let mut state = IntComputer::new(vec![]);
+        state.memory =
+            Memory::from_parts(parse_list(field("cells")?)?, sparse, limit).map_err(invalid)?;
+        state.cursor = cursor;
+        state.relative_base = number("relative_base")?;
+        state.input = parse_list(field("input")?)?.into_iter().collect();
+        state.output = parse_list(field("output")?)?.into_iter().collect();
+        state.steps = optional("steps")?.unwrap_or(0);
+        state.limits.max_steps = optional("max_steps")?;
+        state.limits.detect_loops = detect_loops;
+        state.arithmetic = arithmetic;
+        Ok(Snapshot { state })
+    }
+
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.save(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> io::Result<Snapshot> {
+        Snapshot::load(BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Status;
+
+    #[test]
+    fn restore_test() {
+        let mut intcomp: IntComputer = "1,0,0,0,99".parse().unwrap();
+        let snapshot = intcomp.snapshot();
+        intcomp.run_to_halt().unwrap();
+        assert_eq!(intcomp.memory[0], 2);
+
+        intcomp.restore(&snapshot);
+        assert_eq!(intcomp.memory[0], 1);
+        assert_eq!(intcomp.cursor, 0);
+    }
+
+    #[test]
+    fn save_load_test() {
+        let mut intcomp: IntComputer = "109,7,203,9000,3,0,204,9000,99".parse().unwrap();
+        intcomp.memory.set_limit(Some(100));
+        intcomp.limits.max_steps = Some(1000);
+        intcomp.arithmetic = Arithmetic::Wrapping;
+        intcomp.push_input(42);
+        assert_eq!(intcomp.run().unwrap(), Status::NeedsInput);
+        intcomp.push_input(5);
+        intcomp.push_input(-6);
+
+        let mut text = vec![];
+        intcomp.snapshot().save(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("\ncursor 4\n"));
+        assert!(text.contains("\nsparse 9007=42\n"));
+        assert!(text.contains("\ninput 5,-6\n"));
+        assert!(text.contains("\nsteps 2\nmax_steps 1000\n"));
+
+        let mut restored = IntComputer::new(vec![]);
+        restored.restore(&Snapshot::load(text.as_bytes()).unwrap());
+        assert_eq!(restored, intcomp);
+
+        assert_eq!(restored.run().unwrap(), Status::ProducedOutput);
+        assert_eq!(restored.pop_output(), Some(42));
+    }
+
+    #[test]
+    fn load_error_test() {
+        assert!(Snapshot::load("cursor 1\n".as_bytes()).is_err());
+        let text = format!("{}\ncursor x\n", HEADER);
+        assert!(Snapshot::load(text.as_bytes()).is_err());
+
+        // a sparse cell inside the dense region, and more cells than the
+        // limit allows
+        for (cells, sparse, limit) in &[("1,2,3", "1=5", "-"), ("1,2,3", "10=5", "3")] {
+            let text = format!(
+                "{}\ncursor 0\nrelative_base 0\nlimit {}\ninput\noutput\ncells {}\nsparse {}\n",
+                HEADER, limit, cells, sparse
+            );
+            let err = Snapshot::load(text.as_bytes()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn old_format_test() {
+        let text = format!(
+            "{}\ncursor 0\nrelative_base 0\nlimit -\ninput\noutput\ncells 99\nsparse\n",
+            HEADER
+        );
+        let mut intcomp = IntComputer::new(vec![1, 2]);
+        intcomp.restore(&Snapshot::load(text.as_bytes()).unwrap());
+        assert_eq!(intcomp, "99".parse().unwrap());
+    }
+}
@@ -0,0 +1,507 @@
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+use crate::{Instruction, IntComputer, IntcodeError, Mode, Opcode};
+
+const MAX_STEPS: u64 = 1_000_000;
+
+// Polynomial over the symbolic variables, keyed by the exponent of every
+// variable in the monomial. Zero coefficients are never stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Poly {
+    terms: BTreeMap<Vec<u32>, i64>,
+    nvars: usize,
+}
+
+impl Poly {
+    pub fn constant(value: i64, nvars: usize) -> Poly {
+        let mut terms = BTreeMap::new();
+        if value != 0 {
+            terms.insert(vec![0; nvars], value);
+        }
+        Poly { terms, nvars }
+    }
+
+    pub fn var(index: usize, nvars: usize) -> Poly {
+        let mut exps = vec![0; nvars];
+        exps[index] = 1;
+        let mut terms = BTreeMap::new();
+        terms.insert(exps, 1);
+        Poly { terms, nvars }
+    }
+
+    pub fn as_constant(&self) -> Option<i64> {
+        match self.terms.iter().next() {
+            None => Some(0),
+            Some((exps, &c)) if self.terms.len() == 1 && exps.iter().all(|&e| e == 0) => Some(c),
+            _ => None,
+        }
+    }
+
+    // The arithmetic below returns None when a coefficient, exponent or
+    // value does not fit
+
+    fn add_term(&mut self, exps: Vec<u32>, coeff: i64) -> Option<()> {
+        let c = self.terms.entry(exps.clone()).or_insert(0);
+        *c = c.checked_add(coeff)?;
+        if *c == 0 {
+            self.terms.remove(&exps);
+        }
+        Some(())
+    }
+
+    pub fn add(&self, other: &Poly) -> Option<Poly> {
+        let mut sum = self.clone();
+        for (exps, &c) in &other.terms {
+            sum.add_term(exps.clone(), c)?;
+        }
+        Some(sum)
+    }
+
+    pub fn mul(&self, other: &Poly) -> Option<Poly> {
+        let mut product = Poly::constant(0, self.nvars);
+        for (e1, &c1) in &self.terms {
+            for (e2, &c2) in &other.terms {
+                let exps = e1
+                    .iter()
+                    .zip(e2)
+                    .map(|(a, b)| a.checked_add(*b))
+                    .collect::<Option<_>>()?;
+                product.add_term(exps, c1.checked_mul(c2)?)?;
+            }
+        }
+        Some(product)
+    }
+
+    pub fn eval(&self, values: &[i64]) -> Option<i64> {
+        self.terms.iter().try_fold(0i64, |sum, (exps, &c)| {
+            let term = exps
+                .iter()
+                .zip(values)
+                .try_fold(c, |acc, (&e, &v)| acc.checked_mul(v.checked_pow(e)?))?;
+            sum.checked_add(term)
+        })
+    }
+
+    // Coefficient of every variable plus the constant term, if the
+    // polynomial has degree at most one
+    pub fn linear(&self) -> Option<(Vec<i64>, i64)> {
+        let mut coeffs = vec![0; self.nvars];
+        let mut constant = 0;
+        for (exps, &c) in &self.terms {
+            match exps.iter().sum::<u32>() {
+                0 => constant = c,
+                1 => coeffs[exps.iter().position(|&e| e == 1).unwrap()] = c,
+                _ => return None,
+            }
+        }
+        Some((coeffs, constant))
+    }
+}
+
+impl fmt::Display for Poly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        // highest degree terms first
+        for (i, (exps, &c)) in self.terms.iter().rev().enumerate() {
+            let vars: Vec<String> = exps
+                .iter()
+                .enumerate()
+                .filter(|&(_, &e)| e > 0)
+                .map(|(v, &e)| match e {
+                    1 => format!("x{}", v),
+                    _ => format!("x{}^{}", v, e),
+                })
+                .collect();
+            let sign = if c < 0 { "-" } else { "+" };
+            if i == 0 {
+                write!(f, "{}", if c < 0 { "-" } else { "" })?;
+            } else {
+                write!(f, " {} ", sign)?;
+            }
+            match (c.unsigned_abs(), vars.is_empty()) {
+                (c, true) => write!(f, "{}", c)?,
+                (1, false) => write!(f, "{}", vars.join("*"))?,
+                (c, false) => write!(f, "{}*{}", c, vars.join("*"))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolicError {
+    // A symbolic value reached a spot that needs a concrete number: an
+    // opcode, an address, a jump condition, a comparison or an output.
+    NotConcrete { addr: usize, what: &'static str },
+    Unsupported { addr: usize, opcode: Opcode },
+    // A coefficient no longer fits in an i64
+    Overflow { addr: usize },
+    StepLimit,
+    Fault(IntcodeError),
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SymbolicError::NotConcrete { addr, what } => {
+                write!(f, "symbolic {} at address {}", what, addr)
+            }
+            SymbolicError::Unsupported { addr, opcode } => write!(
+                f,
+                "{} at address {} cannot run symbolically",
+                opcode.mnemonic(),
+                addr
+            ),
+            SymbolicError::Overflow { addr } => {
+                write!(f, "coefficient overflow at address {}", addr)
+            }
+            SymbolicError::StepLimit => write!(f, "symbolic run exceeded {} steps", MAX_STEPS),
+            SymbolicError::Fault(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for SymbolicError {}
+
+impl From<IntcodeError> for SymbolicError {
+    fn from(e: IntcodeError) -> SymbolicError {
+        SymbolicError::Fault(e)
+    }
+}
+
+// Cell contents during a symbolic run. None marks a value that was read
+// through an address depending on a variable; it may be overwritten or never
+// used, but anything that needs it fails.
+pub type Value = Option<Poly>;
+
+// Run a copy of `intcomp` with the cells listed in `vars` replaced by the
+// variables x0, x1, ... and return the final memory.
+pub fn evaluate(
+    intcomp: &IntComputer,
+    vars: &[usize],
+) -> Result<HashMap<usize, Value>, SymbolicError> {
+    let nvars = vars.len();
+    let mut memory: HashMap<usize, Value> = intcomp
+        .memory
+        .iter()
+        .map(|(a, v)| (a, Some(Poly::constant(v, nvars))))
+        .collect();
+    for (i, &addr) in vars.iter().enumerate() {
+        memory.insert(addr, Some(Poly::var(i, nvars)));
+    }
+
+    let mut cursor = intcomp.cursor;
+    let mut relative_base = intcomp.relative_base;
+    for _ in 0..MAX_STEPS {
+        let load = |addr: usize| match memory.get(&addr) {
+            Some(value) => value.clone(),
+            None => Some(Poly::constant(0, nvars)),
+        };
+        let not_concrete = |what| SymbolicError::NotConcrete { addr: cursor, what };
+
+        let raw = load(cursor)
+            .and_then(|p| p.as_constant())
+            .ok_or_else(|| not_concrete("opcode"))?;
+        let instr = Instruction::decode(cursor, raw)?;
+
+        // None when the address depends on a variable
+        let param_addr = |n: usize| -> Result<Option<usize>, SymbolicError> {
+            if instr.modes[n] == Mode::Immediate {
+                return Ok(Some(cursor + 1 + n));
+            }
+            let value = match load(cursor + 1 + n).and_then(|p| p.as_constant()) {
+                Some(value) => value,
+                None => return Ok(None),
+            };
+            let target = match instr.modes[n] {
+                Mode::Position => value,
+                Mode::Relative => relative_base
+                    .checked_add(value)
+                    .ok_or(SymbolicError::Overflow { addr: cursor })?,
+                Mode::Immediate => unreachable!(),
+            };
+            if target < 0 {
+                return Err(IntcodeError::NegativeAddress {
+                    addr: cursor,
+                    op: raw,
+                    target,
+                }
+                .into());
+            }
+            usize::try_from(target)
+                .map(Some)
+                .map_err(|_| SymbolicError::Overflow { addr: cursor })
+        };
+        let read = |n: usize| param_addr(n).map(|addr| addr.and_then(load));
+        let number = |n: usize, what| {
+            read(n)?
+                .and_then(|p| p.as_constant())
+                .ok_or_else(|| not_concrete(what))
+        };
+        let write_addr = |n: usize| -> Result<usize, SymbolicError> {
+            if instr.modes[n] == Mode::Immediate {
+                return Err(IntcodeError::InvalidMode {
+                    addr: cursor,
+                    op: raw,
+                    param: n,
+                    mode: 1,
+                }
+                .into());
+            }
+            param_addr(n)?.ok_or_else(|| not_concrete("address"))
+        };
+
+        let mut next = cursor + instr.size();
+        match instr.opcode {
+            Opcode::Add | Opcode::Mul => {
+                let value = match (read(0)?, read(1)?) {
+                    (Some(a), Some(b)) => {
+                        let value = match instr.opcode {
+                            Opcode::Add => a.add(&b),
+                            _ => a.mul(&b),
+                        };
+                        Some(value.ok_or(SymbolicError::Overflow { addr: cursor })?)
+                    }
+                    _ => None,
+                };
+                let target = write_addr(2)?;
+                memory.insert(target, value);
+            }
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let cond = number(0, "jump condition")?;
+                if (cond != 0) == (instr.opcode == Opcode::JumpIfTrue) {
+                    let target = number(1, "jump target")?;
+                    if target < 0 {
+                        return Err(IntcodeError::NegativeAddress {
+                            addr: cursor,
+                            op: raw,
+                            target,
+                        }
+                        .into());
+                    }
+                    next = usize::try_from(target)
+                        .map_err(|_| SymbolicError::Overflow { addr: cursor })?;
+                }
+            }
+            Opcode::LessThan | Opcode::Equals => {
+                let (a, b) = (number(0, "comparison")?, number(1, "comparison")?);
+                let value = if instr.opcode == Opcode::LessThan {
+                    a < b
+                } else {
+                    a == b
+                };
+                let target = write_addr(2)?;
+                memory.insert(target, Some(Poly::constant(value as i64, nvars)));
+            }
+            Opcode::AdjustBase => {
+                relative_base = relative_base
+                    .checked_add(number(0, "relative base")?)
+                    .ok_or(SymbolicError::Overflow { addr: cursor })?
+            }
+            Opcode::Input | Opcode::Output => {
+                return Err(SymbolicError::Unsupported {
+                    addr: cursor,
+                    opcode: instr.opcode,
+                })
+            }
+            Opcode::Halt => return Ok(memory),
+        }
+        cursor = next;
+    }
+    Err(SymbolicError::StepLimit)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Method {
+    Symbolic(Poly),
+    BruteForce,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    pub noun: i64,
+    pub verb: i64,
+    pub method: Method,
+}
+
+// Whether running with `noun` and `verb` really leaves `target` in cell 0
+fn confirm(intcomp: &IntComputer, noun: i64, verb: i64, target: i64) -> bool {
+    let mut intcomp = intcomp.clone();
+    intcomp.store(1, noun).is_ok()
+        && intcomp.store(2, verb).is_ok()
+        && intcomp.run_to_halt().is_ok()
+        && intcomp.memory[0] == target
+}
+
+// Find the noun (cell 1) and verb (cell 2), both in 0..100, that leave
+// `target` in cell 0. The program is evaluated symbolically once; when the
+// result is linear the equation is solved directly and the answer checked
+// with a real run, otherwise every pair is run as usual.
+pub fn solve_noun_verb(
+    intcomp: &IntComputer,
+    target: i64,
+) -> Result<Option<Solution>, IntcodeError> {
+    let range = 0..100;
+
+    let result = evaluate(intcomp, &[1, 2]).map(|mut memory| memory.remove(&0).flatten());
+    if let Ok(Some(poly)) = result {
+        if let Some((coeffs, constant)) = poly.linear() {
+            let (a, b) = (coeffs[0], coeffs[1]);
+            for noun in range.clone() {
+                let rest = match a
+                    .checked_mul(noun)
+                    .and_then(|an| target.checked_sub(constant)?.checked_sub(an))
+                {
+                    Some(rest) => rest,
+                    None => continue,
+                };
+                let verb = match b {
+                    0 if rest == 0 => range.start,
+                    0 => continue,
+                    b if rest.checked_rem(b) == Some(0) => rest / b,
+                    _ => continue,
+                };
+                if range.contains(&verb) && confirm(intcomp, noun, verb, target) {
+                    let method = Method::Symbolic(poly);
+                    return Ok(Some(Solution { noun, verb, method }));
+                }
+            }
+            return Ok(None);
+        }
+    }
+
+    let initial = intcomp.snapshot();
+    let mut intcomp = intcomp.clone();
+    for noun in range.clone() {
+        for verb in range.clone() {
+            intcomp.restore(&initial);
+            intcomp.store(1, noun)?;
+            intcomp.store(2, verb)?;
+            intcomp.run_to_halt()?;
+            if intcomp.memory[0] == target {
+                let method = Method::BruteForce;
+                return Ok(Some(Solution { noun, verb, method }));
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poly_test() {
+        let x = Poly::var(0, 2);
+        let y = Poly::var(1, 2);
+        let three = Poly::constant(3, 2);
+
+        let p = x.mul(&three).unwrap().add(&y).unwrap().add(&three).unwrap();
+        assert_eq!(p.linear(), Some((vec![3, 1], 3)));
+        assert_eq!(p.eval(&[2, 5]), Some(14));
+        assert_eq!(p.to_string(), "3*x0 + x1 + 3");
+
+        let q = p.mul(&y).unwrap();
+        assert_eq!(q.linear(), None);
+        assert_eq!(q.eval(&[2, 5]), Some(70));
+        assert_eq!(q.to_string(), "3*x0*x1 + x1^2 + 3*x1");
+
+        let min = Poly::constant(i64::MIN, 2).add(&y).unwrap();
+        assert_eq!(min.to_string(), "x1 - 9223372036854775808");
+
+        let zero = p.add(&p.mul(&Poly::constant(-1, 2)).unwrap()).unwrap();
+        assert_eq!(zero.as_constant(), Some(0));
+
+        let big = Poly::constant(i64::MAX, 2);
+        assert_eq!(big.add(&three), None);
+        assert_eq!(big.mul(&three), None);
+        assert_eq!(big.add(&x).unwrap().eval(&[1, 0]), None);
+        assert_eq!(y.mul(&y).unwrap().eval(&[0, i64::MAX]), None);
+    }
+
+    #[test]
+    fn evaluate_test() {
+        // the first add reads through the variables, but its result is
+        // overwritten by [3] = [1] + [2], then [0] = [3] * 3
+        let intcomp: IntComputer = "1,0,0,3,1,1,2,3,1002,3,3,0,99".parse().unwrap();
+        let memory = evaluate(&intcomp, &[1, 2]).unwrap();
+        assert_eq!(memory[&0].as_ref().unwrap().to_string(), "3*x0 + 3*x1");
+
+        // [0] = [[1]] + [[2]] is unknown
+        let intcomp: IntComputer = "1,5,6,0,99,0,0".parse().unwrap();
+        assert_eq!(evaluate(&intcomp, &[1, 2]).unwrap()[&0], None);
+
+        // writing through a variable could clobber anything
+        let intcomp: IntComputer = "1,5,5,0,99,0".parse().unwrap();
+        assert_eq!(
+            evaluate(&intcomp, &[3]),
+            Err(SymbolicError::NotConcrete {
+                addr: 0,
+                what: "address"
+            })
+        );
+    }
+
+    #[test]
+    fn solve_test() {
+        // [0] = [1] * 100 + [2]
+        let intcomp: IntComputer = "1,0,0,3,1002,1,100,3,1,3,2,0,99".parse().unwrap();
+        let solution = solve_noun_verb(&intcomp, 1234).unwrap().unwrap();
+        assert_eq!((solution.noun, solution.verb), (12, 34));
+        assert!(matches!(solution.method, Method::Symbolic(_)));
+        assert_eq!(solve_noun_verb(&intcomp, 100 * 100).unwrap(), None);
+
+        // [0] = [1] * [2] is not linear
+        let intcomp: IntComputer = "1,0,0,3,2,1,2,0,99".parse().unwrap();
+        let solution = solve_noun_verb(&intcomp, 91).unwrap().unwrap();
+        assert_eq!((solution.noun, solution.verb), (1, 91));
+        assert_eq!(solution.method, Method::BruteForce);
+    }
+
+    #[test]
+    fn overflow_test() {
+        // [0] = [1] * 10^27 overflows while evaluating, so every pair is run
+        // and faults the same way
+        let intcomp: IntComputer =
+            "1,0,0,3,1002,1,1000000000,0,1002,0,1000000000,0,1002,0,1000000000,0,99"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            evaluate(&intcomp, &[1, 2]),
+            Err(SymbolicError::Overflow { addr: 12 })
+        );
+        assert_eq!(
+            solve_noun_verb(&intcomp, -6930898827444486144),
+            Err(IntcodeError::Overflow { addr: 12, op: 1002 })
+        );
+
+        // [0] = [1] once [1] * 10^18 cancels out, which only runs for nouns
+        // below 10
+        let intcomp: IntComputer =
+            "1,0,0,3,1002,1,1000000000000000000,24,1002,24,-1,25,1,24,25,26,1,26,1,0,99"
+                .parse()
+                .unwrap();
+        let solution = solve_noun_verb(&intcomp, 5).unwrap().unwrap();
+        assert_eq!((solution.noun, solution.verb), (5, 0));
+        assert!(matches!(solution.method, Method::Symbolic(_)));
+        assert_eq!(solve_noun_verb(&intcomp, 50), Ok(None));
+
+        // relative base arithmetic is checked as well
+        for prog in &[
+            "109,9223372036854775807,109,1,99",
+            "109,-1,22201,-9223372036854775808,0,0,99",
+        ] {
+            let intcomp: IntComputer = prog.parse().unwrap();
+            assert_eq!(
+                evaluate(&intcomp, &[]),
+                Err(SymbolicError::Overflow { addr: 2 })
+            );
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Write};
+
+use crate::{Cell, Instruction, IntComputer, Opcode, Result, Status};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemWrite<C = i64> {
+    pub addr: usize,
+    pub old: C,
+    pub new: C,
+}
+
+// One executed instruction. `operands` holds the value read for every input
+// parameter and the resolved address for the parameter that is written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry<C = i64> {
+    pub cursor: usize,
+    pub op: i64,
+    pub opcode: Opcode,
+    pub operands: Vec<C>,
+    pub write: Option<MemWrite<C>>,
+    pub input: Option<C>,
+    pub output: Option<C>,
+    pub relative_base: i64,
+}
+
+pub trait Tracer<C = i64> {
+    fn trace(&mut self, entry: &TraceEntry<C>);
+}
+
+impl<C: Clone> Tracer<C> for Vec<TraceEntry<C>> {
+    fn trace(&mut self, entry: &TraceEntry<C>) {
+        self.push(entry.clone());
+    }
+}
+
+impl<C: Cell> IntComputer<C> {
+    // Same as `execute_instr`, reporting the instruction to `tracer` once it
+    // has executed. Blocking on input is not reported.
+    pub fn execute_traced(&mut self, tracer: &mut dyn Tracer<C>) -> Result<Option<Status>> {
+        let cursor = self.cursor;
+        let op = self.get_op();
+        let instr = Instruction::decode(cursor, op)?;
+        // blocking on input comes before any operand is looked at, as in
+        // `execute_instr`
+        if instr.opcode == Opcode::Input && self.input.is_empty() {
+            return Ok(Some(Status::NeedsInput));
+        }
+
+        let mut operands = vec![];
+        let mut write = None;
+        for n in 0..instr.opcode.num_params() {
+            if instr.opcode.write_param() == Some(n) {
+                let addr = self.param_addr(&instr, n)?;
+                operands.push(C::from(addr as i64));
+                write = Some((addr, self.memory.get(addr)));
+            } else {
+                operands.push(self.read_param(&instr, n)?);
+            }
+        }
+
+        let status = self.execute_instr()?;
+        if status == Some(Status::NeedsInput) {
+            return Ok(status);
+        }
+
+        let write = write.map(|(addr, old)| MemWrite {
+            addr,
+            old,
+            new: self.memory.get(addr),
+        });
+        let entry = TraceEntry {
+            cursor,
+            op,
+            opcode: instr.opcode,
+            input: match instr.opcode {
+                Opcode::Input => write.as_ref().map(|w| w.new.clone()),
+                _ => None,
+            },
+            output: match instr.opcode {
+                Opcode::Output => Some(operands[0].clone()),
+                _ => None,
+            },
+            operands,
+            write,
+            relative_base: self.relative_base,
+        };
+        tracer.trace(&entry);
+        Ok(status)
+    }
+
+    // Same as `run`, including its limits
+    pub fn run_traced(&mut self, tracer: &mut dyn Tracer<C>) -> Result<Status> {
+        loop {
+            let op = self.get_op();
+            self.check_steps()?;
+            let status = self.execute_traced(tracer)?;
+            if status == Some(Status::NeedsInput) {
+                return Ok(Status::NeedsInput);
+            }
+            self.count_step(op)?;
+            if let Some(status) = status {
+                return Ok(status);
+            }
+        }
+    }
+}
+
+// Writes one JSON object per executed instruction. The first I/O error stops
+// tracing and is returned from `finish`.
+#[cfg(feature = "std")]
+pub struct JsonLinesTracer<W: Write> {
+    out: W,
+    step: u64,
+    error: Option<io::Error>,
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> JsonLinesTracer<W> {
+    pub fn new(out: W) -> JsonLinesTracer<W> {
+        JsonLinesTracer {
+            out,
+            step: 0,
+            error: None,
+        }
+    }
+
+    fn write_entry<C: Cell>(&mut self, entry: &TraceEntry<C>) -> io::Result<()> {
+        let operands: Vec<String> = entry.operands.iter().map(|v| v.to_string()).collect();
+        write!(
+            self.out,
+            "{{\"step\":{},\"cursor\":{},\"op\":{},\"mnemonic\":\"{}\",\"operands\":[{}]",
+            self.step,
+            entry.cursor,
+            entry.op,
+            entry.opcode.mnemonic(),
+            operands.join(",")
+        )?;
+        if let Some(ref w) = entry.write {
+            write!(
+                self.out,
+                ",\"write\":{{\"addr\":{},\"old\":{},\"new\":{}}}",
+                w.addr, w.old, w.new
+            )?;
+        }
+        if let Some(ref v) = entry.input {
+            write!(self.out, ",\"input\":{}", v)?;
+        }
+        if let Some(ref v) = entry.output {
+            write!(self.out, ",\"output\":{}", v)?;
+        }
+        writeln!(self.out, ",\"relative_base\":{}}}", entry.relative_base)
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: Write, C: Cell> Tracer<C> for JsonLinesTracer<W> {
+    fn trace(&mut self, entry: &TraceEntry<C>) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = self.write_entry(entry) {
+            self.error = Some(e);
+        }
+        self.step += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_test() {
+        let mut intcomp: IntComputer = "3,9,1002,9,2,9,4,9,99,0".parse().unwrap();
+        let mut entries = vec![];
+        assert_eq!(
+            intcomp.run_traced(&mut entries).unwrap(),
+            Status::NeedsInput
+        );
+        assert!(entries.is_empty());
+
+        intcomp.push_input(5);
+        intcomp.run_traced(&mut entries).unwrap();
+        intcomp.run_traced(&mut entries).unwrap();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].input, Some(5));
+        assert_eq!(entries[1].operands, [5, 2, 9]);
+        assert_eq!(
+            entries[1].write,
+            Some(MemWrite {
+                addr: 9,
+                old: 5,
+                new: 10
+            })
+        );
+        assert_eq!(entries[2].output, Some(10));
+        assert_eq!(entries[3].opcode, Opcode::Halt);
+    }
+
+    #[test]
+    fn blocked_input_test() {
+        // `in [-1]` waits for input before it notices the bad address
+        let mut plain: IntComputer = "3,-1,99".parse().unwrap();
+        let mut traced = plain.clone();
+        assert_eq!(plain.run(), Ok(Status::NeedsInput));
+        assert_eq!(traced.run_traced(&mut vec![]), Ok(Status::NeedsInput));
+
+        plain.push_input(1);
+        traced.push_input(1);
+        assert_eq!(traced.run_traced(&mut vec![]), plain.run());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn json_lines_test() {
+        let mut intcomp: IntComputer = "109,-2,204,3,99".parse().unwrap();
+        let mut tracer = JsonLinesTracer::new(vec![]);
+        intcomp.run_traced(&mut tracer).unwrap();
+        intcomp.run_traced(&mut tracer).unwrap();
+
+        let text = String::from_utf8(tracer.finish().unwrap()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                r#"{"step":0,"cursor":0,"op":109,"mnemonic":"arb","operands":[-2],"relative_base":-2}"#,
+                r#"{"step":1,"cursor":2,"op":204,"mnemonic":"out","operands":[-2],"output":-2,"relative_base":-2}"#,
+                r#"{"step":2,"cursor":4,"op":99,"mnemonic":"hlt","operands":[],"relative_base":-2}"#,
+            ]
+        );
+    }
+}